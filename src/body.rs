@@ -0,0 +1,154 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use hyper::{Body, HeaderMap, Response};
+use http_body::SizeHint;
+use hyper::body::{Bytes, HttpBody};
+use tokio::sync::oneshot;
+
+
+pub enum BodyError {
+    /// Body exceeds the allowed size.
+    TooLarge,
+    /// Body could not be read from the connection.
+    Failed(String),
+}
+
+impl BodyError {
+    pub fn describe(&self, limit: usize) -> String {
+        //! Returns human readable description of error.
+        match self {
+            BodyError::TooLarge => format!("Body is larger than {} bytes", limit),
+            BodyError::Failed(error) => error.to_owned(),
+        }
+    }
+}
+
+/// Trailers sent after body of response. Views put them into response extensions,
+/// since `Body` created by gateway cannot carry trailers by itself.
+pub struct Trailers(pub oneshot::Receiver<HeaderMap>);
+
+/// Body of responses returned by gateway, which ends with `Trailers` if response has them.
+/// Trailers are sent over HTTP/2 only, HTTP/1.1 clients never receive them.
+pub struct ResponseBody {
+    body: Body,
+    trailers: Option<oneshot::Receiver<HeaderMap>>,
+}
+
+impl ResponseBody {
+    pub fn from_response(response: Response<Body>) -> Response<ResponseBody> {
+        //! Wraps body of response together with `Trailers` from its extensions.
+        let (mut parts, body) = response.into_parts();
+        let trailers = parts.extensions.remove::<Trailers>().map(|trailers| trailers.0);
+        Response::from_parts(parts, ResponseBody { body, trailers })
+    }
+}
+
+impl HttpBody for ResponseBody {
+    type Data = Bytes;
+    type Error = hyper::Error;
+
+    fn poll_data(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Bytes, hyper::Error>>> {
+        Pin::new(&mut self.get_mut().body).poll_data(cx)
+    }
+
+    fn poll_trailers(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<Option<HeaderMap>, hyper::Error>> {
+        let response_body = self.get_mut();
+        match response_body.trailers.as_mut() {
+            // Trailers are never sent, if their sender is dropped.
+            Some(trailers) => Pin::new(trailers).poll(cx).map(|trailers| Ok(trailers.ok())),
+            None => Pin::new(&mut response_body.body).poll_trailers(cx),
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.trailers.is_none() && self.body.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.body.size_hint()
+    }
+}
+
+pub async fn read_body_bytes(mut body: Body, limit: usize) -> Result<Bytes, BodyError> {
+    //! Buffers whole body in memory, failing as soon as it exceeds `limit`.
+    let mut buffer: Vec<u8> = vec![];
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|e|BodyError::Failed(e.to_string()))?;
+        if buffer.len() + chunk.len() > limit {
+            return Err(BodyError::TooLarge);
+        }
+        buffer.extend_from_slice(&chunk);
+    }
+    Ok(Bytes::from(buffer))
+}
+
+pub fn body_to_data(body: &[u8]) -> Option<String> {
+    //! Converts body into trimmed text, the way it is matched and stored in DB.
+    //! Empty body is `None`.
+    let text = String::from_utf8_lossy(body);
+    let trimmed = text.trim();
+    if trimmed.is_empty() { None } else { Some(trimmed.to_owned()) }
+}
+
+pub fn capture_body(
+    mut body: Body,
+    limit: usize,
+    read_timeout: Option<Duration>
+) -> (Body, Trailers, oneshot::Receiver<Option<Bytes>>) {
+    //! Streams `body` through a new body, keeping copy of passed data
+    //! for journal. Copy is sent to returned receiver, once the body is
+    //! streamed completely. It is `None` if body is larger than `limit`,
+    //! or streaming failed. Streaming is aborted, if no data are received
+    //! within `read_timeout`. Trailers of `body` are passed to `Trailers`.
+    let (mut sender, streamed_body) = Body::channel();
+    let (trailers_sender, trailers_receiver) = oneshot::channel();
+    let (captured_sender, captured_receiver) = oneshot::channel();
+
+    tokio::spawn(async move {
+        let mut captured: Option<Vec<u8>> = Some(vec![]);
+        loop {
+            let chunk = match read_timeout {
+                Some(read_timeout) => match tokio::time::timeout(read_timeout, body.data()).await {
+                    Ok(chunk) => chunk,
+                    Err(_) => {
+                        println!("[+] Streaming of body timed out after {} ms", read_timeout.as_millis());
+                        sender.abort();
+                        let _ = captured_sender.send(None);
+                        return;
+                    }
+                },
+                None => body.data().await,
+            };
+            let chunk = match chunk {
+                None => break,
+                Some(Ok(chunk)) => chunk,
+                Some(Err(error)) => {
+                    println!("[+] Streaming of body failed: {}", error);
+                    sender.abort();
+                    let _ = captured_sender.send(None);
+                    return;
+                }
+            };
+            if captured.as_ref().is_some_and(|buffer|buffer.len() + chunk.len() > limit) {
+                captured = None;
+            }
+            if let Some(buffer) = captured.as_mut() {
+                buffer.extend_from_slice(&chunk);
+            }
+            if sender.send_data(chunk).await.is_err() {
+                // Receiving side hung up, e.g. client closed connection.
+                let _ = captured_sender.send(None);
+                return;
+            }
+        }
+        if let Ok(Some(trailers)) = body.trailers().await {
+            let _ = trailers_sender.send(trailers);
+        }
+        let _ = captured_sender.send(captured.map(Bytes::from));
+    });
+
+    (streamed_body, Trailers(trailers_receiver), captured_receiver)
+}
@@ -0,0 +1,281 @@
+use hyper::{Body, HeaderMap, Method, Response};
+use hyper::header::{self, HeaderValue};
+use hyper::http::StatusCode;
+use lazy_static::lazy_static;
+use serde_json::{json, Value as JsonValue};
+
+use crate::errors::FieldError;
+use crate::settings;
+use crate::utils::json_message;
+
+lazy_static! {
+    /// Policy configured by `ROCKERY_CORS_*` env variables, applied unless rule overrides it.
+    static ref GLOBAL_POLICY: Option<CorsPolicy> = settings::CORS_ALLOWED_ORIGINS.as_ref().map(
+        |allowed_origins| CorsPolicy {
+            allowed_origins: allowed_origins.to_owned(),
+            allow_credentials: *settings::CORS_ALLOW_CREDENTIALS,
+            allowed_methods: settings::CORS_ALLOWED_METHODS.clone(),
+            allowed_headers: settings::CORS_ALLOWED_HEADERS.clone(),
+            expose_headers: settings::CORS_EXPOSE_HEADERS.clone(),
+            max_age: *settings::CORS_MAX_AGE,
+        }
+    );
+}
+
+/// CORS headers of mocked responses, unless CORS is configured globally or by rule.
+const DEFAULT_MOCK_HEADERS: [(header::HeaderName, &str); 3] = [
+    (header::ACCESS_CONTROL_ALLOW_ORIGIN, "*"),
+    (header::ACCESS_CONTROL_ALLOW_HEADERS, "*"),
+    (header::ACCESS_CONTROL_ALLOW_METHODS, "GET, PUT, POST, DELETE, HEAD, OPTIONS"),
+];
+
+/// Origins, methods and headers allowed to CORS requests.
+#[derive(Debug, Clone)]
+pub struct CorsPolicy {
+    /// Allowed origins, `*` allows any.
+    pub allowed_origins: Vec<String>,
+    pub allow_credentials: bool,
+    /// Comma-separated methods, requested method is allowed if not set.
+    pub allowed_methods: Option<String>,
+    /// Comma-separated headers, requested headers are allowed if not set.
+    pub allowed_headers: Option<String>,
+    pub expose_headers: Option<String>,
+    pub max_age: Option<u64>,
+}
+
+impl CorsPolicy {
+    fn from_json(parsed_json: &JsonValue) -> Option<CorsPolicy> {
+        //! Deserializes policy stored by `get_json_response_cors`.
+        let join = |value: &JsonValue| value.as_array().map(
+            |values| values.iter().filter_map(|value| value.as_str()).collect::<Vec<&str>>().join(", ")
+        );
+        Some(
+            CorsPolicy {
+                allowed_origins: parsed_json["allowed_origins"].as_array()?.iter()
+                    .filter_map(|origin| origin.as_str().map(|origin| origin.to_owned()))
+                    .collect(),
+                allow_credentials: parsed_json["allow_credentials"].as_bool().unwrap_or(false),
+                allowed_methods: join(&parsed_json["allowed_methods"]),
+                allowed_headers: join(&parsed_json["allowed_headers"]),
+                expose_headers: join(&parsed_json["expose_headers"]),
+                max_age: parsed_json["max_age"].as_u64(),
+            }
+        )
+    }
+
+    fn allowed_origin(&self, origin: Option<&str>) -> Option<String> {
+        //! Returns value of `Access-Control-Allow-Origin` for request from `origin`, if it is allowed.
+        //! Any origin is echoed, if credentials are allowed, since browsers reject `*` for them.
+        let any_origin = self.allowed_origins.iter().any(|allowed_origin| allowed_origin == "*");
+        match origin {
+            Some(origin) if any_origin && self.allow_credentials => Some(origin.to_owned()),
+            _ if any_origin => Some("*".to_owned()),
+            Some(origin) if self.allowed_origins.iter().any(|allowed_origin| allowed_origin == origin) => Some(origin.to_owned()),
+            _ => None,
+        }
+    }
+}
+
+pub fn get_json_response_cors(
+    parsed_json: &JsonValue,
+    field_name: &str,
+    error_messages: &mut Vec<FieldError>
+) -> Option<String>{
+    //! Extracts and validates CORS policy of rule. It is either `false`, which disables
+    //! CORS for rule, or an object with `allowed_origins` (array or `*`) and optional
+    //! `allow_credentials`, `allowed_methods`, `allowed_headers`, `expose_headers` and `max_age`.
+    let cors = match parsed_json.get(field_name) {
+        Some(JsonValue::Null) | None => return None,
+        Some(JsonValue::Bool(false)) => return Some(JsonValue::Bool(false).to_string()),
+        Some(JsonValue::Object(cors)) => cors,
+        Some(_) => {
+            error_messages.push(FieldError::new(field_name, format!("{} must be an object or false", field_name)));
+            return None;
+        }
+    };
+
+    let allowed_origins = match cors.get("allowed_origins") {
+        Some(JsonValue::String(origin)) if origin == "*" => vec![JsonValue::String(origin.to_owned())],
+        Some(JsonValue::Array(origins)) if !origins.is_empty() && origins.iter().all(|origin| origin.is_string()) => origins.to_owned(),
+        _ => {
+            error_messages.push(FieldError::new(field_name, format!("{} must contain allowed_origins as non-empty array of origins or *", field_name)));
+            vec![]
+        }
+    };
+    for name in ["allowed_methods", "allowed_headers", "expose_headers"].iter() {
+        match cors.get(*name) {
+            Some(JsonValue::Array(values)) if values.iter().all(|value| value.is_string()) => (),
+            Some(JsonValue::Null) | None => (),
+            Some(_) => error_messages.push(FieldError::new(field_name, format!("{} must contain {} as array of strings", field_name, name))),
+        }
+    }
+    if cors.get("allow_credentials").is_some_and(|allow_credentials| !allow_credentials.is_boolean()) {
+        error_messages.push(FieldError::new(field_name, format!("{} must contain allow_credentials as boolean", field_name)));
+    }
+    if cors.get("max_age").is_some_and(|max_age| !max_age.is_u64()) {
+        error_messages.push(FieldError::new(field_name, format!("{} must contain max_age as non-negative integer", field_name)));
+    }
+
+    Some(
+        json!({
+            "allowed_origins": allowed_origins,
+            "allow_credentials": cors.get("allow_credentials"),
+            "allowed_methods": cors.get("allowed_methods"),
+            "allowed_headers": cors.get("allowed_headers"),
+            "expose_headers": cors.get("expose_headers"),
+            "max_age": cors.get("max_age"),
+        }).to_string()
+    )
+}
+
+pub fn resolve_policy(rule_cors: &Option<String>) -> Option<CorsPolicy> {
+    //! Returns policy of rule, or global policy if rule does not override it.
+    match rule_cors.as_ref().map(|rule_cors| serde_json::from_str::<JsonValue>(rule_cors)) {
+        Some(Ok(JsonValue::Bool(false))) => None,
+        Some(Ok(rule_cors)) => CorsPolicy::from_json(&rule_cors),
+        _ => GLOBAL_POLICY.clone(),
+    }
+}
+
+pub fn is_preflight(method: &Method, headers: &HeaderMap) -> bool {
+    //! Checks if request is CORS preflight.
+    method == Method::OPTIONS
+        && headers.contains_key(header::ORIGIN)
+        && headers.contains_key(header::ACCESS_CONTROL_REQUEST_METHOD)
+}
+
+pub fn get_requested_method(headers: &HeaderMap) -> Option<Method> {
+    //! Reads method, which preflight asks for.
+    headers.get(header::ACCESS_CONTROL_REQUEST_METHOD)?.to_str().ok()?.parse().ok()
+}
+
+fn insert_header(headers: &mut HeaderMap, name: header::HeaderName, value: &str) {
+    //! Inserts header, values which are not valid header values are skipped.
+    if let Ok(value) = HeaderValue::from_str(value) {
+        headers.insert(name, value);
+    }
+}
+
+pub fn apply_cors_headers(policy: &CorsPolicy, origin: Option<&str>, response_headers: &mut HeaderMap) {
+    //! Replaces CORS headers of response with the ones of `policy`.
+    let existing_headers : Vec<header::HeaderName> = response_headers.keys()
+        .filter(|name| name.as_str().starts_with("access-control-"))
+        .cloned()
+        .collect();
+    for name in existing_headers {
+        response_headers.remove(name);
+    }
+
+    let allowed_origin = match policy.allowed_origin(origin) {
+        Some(allowed_origin) => allowed_origin,
+        None => return,
+    };
+    if allowed_origin != "*" {
+        response_headers.append(header::VARY, HeaderValue::from_static("Origin"));
+    }
+    insert_header(response_headers, header::ACCESS_CONTROL_ALLOW_ORIGIN, &allowed_origin);
+    if policy.allow_credentials {
+        response_headers.insert(header::ACCESS_CONTROL_ALLOW_CREDENTIALS, HeaderValue::from_static("true"));
+    }
+    if let Some(expose_headers) = &policy.expose_headers {
+        insert_header(response_headers, header::ACCESS_CONTROL_EXPOSE_HEADERS, expose_headers);
+    }
+}
+
+pub fn apply_mock_cors_headers(rule_cors: &Option<String>, origin: Option<&str>, response_headers: &mut HeaderMap) {
+    //! Sets CORS headers of mocked response. Mocked responses allow any origin, unless
+    //! CORS policy is configured globally or by rule.
+    match resolve_policy(rule_cors) {
+        Some(policy) => apply_cors_headers(&policy, origin, response_headers),
+        None if rule_cors.is_none() => for (name, value) in DEFAULT_MOCK_HEADERS.iter() {
+            response_headers.insert(name, HeaderValue::from_static(value));
+        },
+        None => (),
+    }
+}
+
+pub fn preflight_response(policy: &CorsPolicy, request_headers: &HeaderMap) -> Response<Body> {
+    //! Answers preflight. Preflights from origins, which are not allowed, are forbidden.
+    let origin = request_headers.get(header::ORIGIN).and_then(|origin| origin.to_str().ok());
+    if policy.allowed_origin(origin).is_none() {
+        let mut response = Response::new(Body::from(json_message("Origin is not allowed by CORS policy")));
+        *(response.status_mut()) = StatusCode::FORBIDDEN;
+        response.headers_mut().insert(header::CONTENT_TYPE, HeaderValue::from_static("application/json; charset=UTF-8"));
+        return response;
+    }
+
+    let mut response = Response::new(Body::empty());
+    *(response.status_mut()) = StatusCode::NO_CONTENT;
+    let headers = response.headers_mut();
+    apply_cors_headers(policy, origin, headers);
+    headers.remove(header::ACCESS_CONTROL_EXPOSE_HEADERS);
+
+    let requested = |name: header::HeaderName| request_headers.get(name).and_then(|value| value.to_str().ok()).map(|value| value.to_owned());
+    if let Some(allowed_methods) = policy.allowed_methods.clone().or_else(|| requested(header::ACCESS_CONTROL_REQUEST_METHOD)) {
+        insert_header(headers, header::ACCESS_CONTROL_ALLOW_METHODS, &allowed_methods);
+    }
+    if let Some(allowed_headers) = policy.allowed_headers.clone().or_else(|| requested(header::ACCESS_CONTROL_REQUEST_HEADERS)) {
+        insert_header(headers, header::ACCESS_CONTROL_ALLOW_HEADERS, &allowed_headers);
+    }
+    if let Some(max_age) = policy.max_age {
+        headers.insert(header::ACCESS_CONTROL_MAX_AGE, HeaderValue::from(max_age));
+    }
+    headers.insert(header::SERVER, HeaderValue::from_static("Rockery - Rust Mocking Gateway"));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(allowed_origins: &[&str], allow_credentials: bool) -> CorsPolicy {
+        CorsPolicy {
+            allowed_origins: allowed_origins.iter().map(|origin| origin.to_string()).collect(),
+            allow_credentials,
+            allowed_methods: None,
+            allowed_headers: None,
+            expose_headers: None,
+            max_age: None,
+        }
+    }
+
+    #[test]
+    fn allows_any_origin_with_wildcard() {
+        let policy = policy(&["*"], false);
+
+        assert_eq!(policy.allowed_origin(Some("http://a.test")).as_deref(), Some("*"));
+        assert_eq!(policy.allowed_origin(None).as_deref(), Some("*"));
+    }
+
+    #[test]
+    fn echoes_any_origin_when_credentials_are_allowed() {
+        let policy = policy(&["*"], true);
+
+        assert_eq!(policy.allowed_origin(Some("http://a.test")).as_deref(), Some("http://a.test"));
+        assert_eq!(policy.allowed_origin(None).as_deref(), Some("*"));
+    }
+
+    #[test]
+    fn allows_listed_origins_only() {
+        let policy = policy(&["http://a.test", "http://b.test"], false);
+
+        assert_eq!(policy.allowed_origin(Some("http://b.test")).as_deref(), Some("http://b.test"));
+        assert_eq!(policy.allowed_origin(Some("http://c.test")), None);
+        assert_eq!(policy.allowed_origin(None), None);
+    }
+
+    #[test]
+    fn replaces_cors_headers_of_response() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCESS_CONTROL_ALLOW_METHODS, HeaderValue::from_static("GET"));
+
+        apply_cors_headers(&policy(&["http://a.test"], true), Some("http://a.test"), &mut headers);
+        assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_ORIGIN], "http://a.test");
+        assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_CREDENTIALS], "true");
+        assert_eq!(headers[header::VARY], "Origin");
+        assert!(!headers.contains_key(header::ACCESS_CONTROL_ALLOW_METHODS));
+
+        apply_cors_headers(&policy(&["http://a.test"], false), Some("http://c.test"), &mut headers);
+        assert!(headers.keys().all(|name| !name.as_str().starts_with("access-control-")));
+    }
+}
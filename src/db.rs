@@ -1,28 +1,194 @@
 use core::str::FromStr;
 
 use hyper::Method;
-use rusqlite::{params, NO_PARAMS, types::FromSqlError};
+use rusqlite::{params, NO_PARAMS, OptionalExtension, Row, ToSql, types::FromSqlError};
+use serde_json::{json, Value as JsonValue};
 
+use crate::errors::DbError;
 use crate::settings;
 
+/// Method of rules, which match requests regardless of their method.
+pub const ANY_METHOD: &str = "ANY";
+
+/// Model stored in its own db table with `id` primary key. Implementors describe
+/// columns of table and mapping of rows, queries are shared by every model.
+pub trait Record: Sized {
+    /// Defines name of db table of model
+    const TABLE_NAME: &'static str;
+    /// Name of model used in messages, e.g. `gRPC rule`.
+    const DISPLAY_NAME: &'static str;
+    /// Columns of db table besides `id` and their SQL types, in order of `values`.
+    const COLUMNS: &'static [(&'static str, &'static str)];
+    /// Order of records returned by `select`.
+    const ORDER_BY: &'static str = "id";
+
+    fn id(&self) -> Option<i64>;
+
+    fn set_id(&mut self, id: i64);
+
+    /// Values of `COLUMNS` saved by `create`.
+    fn values(&self) -> Vec<Box<dyn ToSql + '_>>;
+
+    fn from_row(row: &Row) -> rusqlite::Result<Self>;
+
+    fn as_json(&self) -> JsonValue;
+
+    fn check_duplicate(&self) -> Result<(), DbError> {
+        //! Rejects records conflicting with existing ones. Records never conflict by default.
+        Ok(())
+    }
+
+    fn describe(&self) -> String {
+        //! Describes record in messages, e.g. `gRPC rule #1`.
+        format!("{} #{}", Self::DISPLAY_NAME, display_id(self.id()))
+    }
+
+    fn create_db_table() -> Result<(), DbError>{
+        //! Creates a new DB for model
+        let columns: Vec<String> = Self::COLUMNS.iter().map(
+            |(name, sql_type)| format!("{} {}", name, sql_type)
+        ).collect();
+        let conn = settings::DB.lock().unwrap();
+        conn.execute(
+            &format!(
+                "CREATE TABLE IF NOT EXISTS {} (id INTEGER PRIMARY KEY, {})",
+                Self::TABLE_NAME, columns.join(", ")
+            ),
+            params![],
+        )?;
+        Ok(())
+    }
+
+    fn create(&mut self) -> Result<(), DbError> {
+        //! Saves instantiated, nonexistent record.
+        if self.id().is_some() {
+            return Err(DbError::Invalid(format!(
+                "{} already exists. Cannot create records with already existing ID", Self::DISPLAY_NAME
+            )));
+        }
+        self.check_duplicate()?;
+
+        let columns: Vec<&str> = Self::COLUMNS.iter().map(|(name, _)| *name).collect();
+        let conn = settings::DB.lock().unwrap();
+        let query_result_count = conn.execute(
+            &format!(
+                "INSERT INTO {} ({}) VALUES ({})",
+                Self::TABLE_NAME, columns.join(", "), vec!["?"; columns.len()].join(", ")
+            ),
+            self.values(),
+        )?;
+
+        if query_result_count == 0 {
+            return Err(DbError::Invalid("Database failed to perform insert".to_owned()));
+        }
+        self.set_id(conn.last_insert_rowid());
+        Ok(())
+    }
+
+    fn select(where_clause: &str, values: &[&dyn ToSql]) -> Result<Vec<Self>, DbError> {
+        //! Selects records matching `where_clause`, in `ORDER_BY` order.
+        let conn = settings::DB.lock().unwrap();
+        let mut stmt = conn.prepare(
+            &format!("SELECT * FROM {} WHERE {} ORDER BY {};", Self::TABLE_NAME, where_clause, Self::ORDER_BY)
+        )?;
+
+        let results = stmt.query_map(values, Self::from_row)?;
+
+        let mut output : Vec<Self> = vec![];
+        for record in results{
+            output.push(
+                record?
+            );
+        }
+        Ok(output)
+    }
+
+    fn all() -> Result<Vec<Self>, DbError>{
+        //! Returns all records in order they are evaluated.
+        Self::select("1 = 1", NO_PARAMS)
+    }
+
+    fn find_by_id(id: i64) -> Result<Option<Self>, DbError>{
+        //! Finds record by its `id`.
+        Ok(Self::select("id = ?", params![id])?.pop())
+    }
+
+    fn delete(&mut self) -> Result<(), DbError> {
+        //! Deletes record from a database.
+        if self.id().is_none() {
+            return Err(DbError::Invalid(format!(
+                "Cannot delete {} which does not exist in database.", Self::DISPLAY_NAME
+            )));
+        }
+
+        let conn = settings::DB.lock().unwrap();
+        let query_result_count = conn.execute(
+            &format!("DELETE FROM {} WHERE id = ? ;", Self::TABLE_NAME),
+            params![self.id()],
+        )?;
+
+        if query_result_count == 0 {
+            return Err(DbError::NotFound("Record has already been deleted".to_owned()));
+        }
+        Ok(())
+    }
+}
+
+fn display_id(id: Option<i64>) -> String {
+    //! Displays `id` as string
+    match id {
+        Some(id) => id.to_string(),
+        None => "<None>".to_string()
+    }
+}
+
+fn method_from_row(row: &Row) -> rusqlite::Result<Option<Method>> {
+    //! Reads optional `method` column of rules, which match requests of any method when it is NULL.
+    let method : Option<String> = row.get(row.column_index("method")?)?;
+    match method {
+        Some(method) => Ok(Some(
+            Method::from_str(&method).map_err(|e| FromSqlError::Other(Box::new(e)))?
+        )),
+        None => Ok(None),
+    }
+}
+
 /// Simple ORM for mocking rules
 #[derive(Debug)]
 pub struct MockingRule {
     // TODO: Optimize `String` props to `&str` if possible
     pub id: Option<i64>,
     pub request_method: Method,
+    pub request_host: Option<String>,
     pub request_url: String,
     pub request_query: Option<String>,
     pub request_data: Option<String>,
+    /// Serialized JSON object of form fields, which body must contain.
+    pub request_form: Option<String>,
+    /// Serialized JSON object of conditions on files uploaded in multipart body.
+    pub request_files: Option<String>,
+    /// Serialized JSON array of XPath expressions, which must be true for XML body.
+    pub request_xpath: Option<String>,
+    /// Serialized JSON object of namespace prefixes used in `request_xpath`.
+    pub request_namespaces: Option<String>,
+    pub request_soap_action: Option<String>,
     pub response_status_code: i64,
     pub response_data: Option<String>,
+    /// Content type of response, JSON is used by default.
+    pub response_content_type: Option<String>,
+    /// Serialized JSON array of chunks streamed instead of `response_data`.
+    pub response_chunks: Option<String>,
+    /// Chunks are streamed as Server-Sent Events.
+    pub response_sse: bool,
+    /// Serialized CORS policy overriding global one, `false` disables CORS.
+    pub response_cors: Option<String>,
 }
 
 impl MockingRule {
     /// Defines name of db table for `MockingRule` model
     const TABLE_NAME: &'static str = "mocking_rules";
 
-    pub fn create_db_table() -> Result<(), String>{
+    pub fn create_db_table() -> Result<(), DbError>{
         //! Creates a new DB for model
 
         let conn = settings::DB.lock().unwrap();
@@ -31,11 +197,21 @@ impl MockingRule {
             "CREATE TABLE IF NOT EXISTS {} (
                 id                      INTEGER PRIMARY KEY,
                 request_method          TEXT NOT NULL,
+                request_host            TEXT,
                 request_url             TEXT NOT NULL,
                 request_query           TEXT,
                 request_data            TEXT,
+                request_form            TEXT,
+                request_files           TEXT,
+                request_xpath           TEXT,
+                request_namespaces      TEXT,
+                request_soap_action     TEXT,
                 response_status_code    INTEGER NOT NULL,
-                response_data           TEXT
+                response_data           TEXT,
+                response_content_type   TEXT,
+                response_chunks         TEXT,
+                response_sse            INTEGER NOT NULL,
+                response_cors           TEXT
             )", Self::TABLE_NAME),
             params![],
         ) {
@@ -44,19 +220,20 @@ impl MockingRule {
         };
     }
 
-    pub fn create(&mut self) -> Result<(), String> {
+    pub fn create(&mut self) -> Result<(), DbError> {
         //! Saves instantiated, nonexistent `MockingRule` record.
         if self.id.is_some() {
-            return Err("MockingRule already exists. Cannot create records with already existing ID".to_owned());
+            return Err(DbError::Invalid("MockingRule already exists. Cannot create records with already existing ID".to_owned()));
         }
         
         if Self::find(
             &Some(self.request_url.clone()),
             &self.request_query,
             &Some(self.request_method.clone()),
-            &self.request_data
-        )?.len() > 0 {
-            return Err("Rule on this endpoint already exists!".to_owned());
+            &self.request_data,
+            &self.request_host
+        )?.iter().any(|rule| rule.same_body_matchers(self)) {
+            return Err(DbError::Duplicate("Rule on this endpoint already exists!".to_owned()));
         }
 
 
@@ -66,42 +243,62 @@ impl MockingRule {
             &format!("
                 INSERT INTO {} (
                     request_method,
+                    request_host,
                     request_url,
                     request_query,
                     request_data,
+                    request_form,
+                    request_files,
+                    request_xpath,
+                    request_namespaces,
+                    request_soap_action,
                     response_status_code,
-                    response_data)
+                    response_data,
+                    response_content_type,
+                    response_chunks,
+                    response_sse,
+                    response_cors)
                 VALUES
-                    (?, ?, ?, ?, ?, ?)",
+                    (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
                 Self::TABLE_NAME
             ), params![
                 self.request_method.as_str(),
+                self.request_host,
                 self.request_url,
                 self.request_query,
                 self.request_data,
+                self.request_form,
+                self.request_files,
+                self.request_xpath,
+                self.request_namespaces,
+                self.request_soap_action,
                 self.response_status_code,
-                self.response_data
+                self.response_data,
+                self.response_content_type,
+                self.response_chunks,
+                self.response_sse,
+                self.response_cors
             ],
         ) {
             Ok(query_result_count) => {
                 if query_result_count == 0{
-                    return Err("Database failed to perform insert".to_owned());
+                    return Err(DbError::Invalid("Database failed to perform insert".to_owned()));
                 }
                 self.id = Some(conn.last_insert_rowid());
                 Ok(())
             },
-            Err(e) => Err(e.to_string())
+            Err(e) => Err(e.into())
         }
     }
 
-    pub fn count_all() -> Result<i64, String> {
+    pub fn count_all() -> Result<i64, DbError> {
         //! Counts total count of all MockingRule records stored in database.
         let conn = settings::DB.lock().unwrap();
         let mut query_statement = conn.prepare(
             &format!("SELECT Count(*) FROM {}", Self::TABLE_NAME)
-        ).map_err(|e|e.to_string())?;
+        )?;
 
-        let mut query_result = query_statement.query(NO_PARAMS).map_err(|e|e.to_string())?;
+        let mut query_result = query_statement.query(NO_PARAMS)?;
         
         match query_result.next() {
             Ok(row) => {
@@ -109,23 +306,89 @@ impl MockingRule {
                     Some(r) => {
                         return match r.get(0) {
                             Ok(val) => Ok(val),
-                            Err(e) => Err(e.to_string())
+                            Err(e) => Err(e.into())
                         };
                     },
-                    None => Err("Database Count statement returned no result".to_owned())
+                    None => Err(DbError::Invalid("Database Count statement returned no result".to_owned()))
                 };
             },
-            Err(e) => return Err(e.to_string())
+            Err(e) => return Err(e.into())
         }
     }
     
+    fn from_row(row: &Row) -> rusqlite::Result<MockingRule> {
+        //! Maps a database row onto `MockingRule`.
+        let id : Option<i64> = row.get(row.column_index("id")?)?;
+        let request_method_raw : String = row.get(row.column_index("request_method")?)?;
+        let request_method : Method = Method::from_str(&request_method_raw).map_err(
+            |e| FromSqlError::Other(Box::new(e))
+        )?;
+
+        let request_host : Option<String> = row.get(row.column_index("request_host")?)?;
+        let request_url : String = row.get(row.column_index("request_url")?)?;
+        let request_query : Option<String> = row.get(row.column_index("request_query")?)?;
+        let request_data : Option<String> = row.get(row.column_index("request_data")?)?;
+        let request_form : Option<String> = row.get(row.column_index("request_form")?)?;
+        let request_files : Option<String> = row.get(row.column_index("request_files")?)?;
+        let request_xpath : Option<String> = row.get(row.column_index("request_xpath")?)?;
+        let request_namespaces : Option<String> = row.get(row.column_index("request_namespaces")?)?;
+        let request_soap_action : Option<String> = row.get(row.column_index("request_soap_action")?)?;
+
+        let response_status_code : i64 = row.get(row.column_index("response_status_code")?)?;
+        let response_data : Option<String> = row.get(row.column_index("response_data")?)?;
+        let response_content_type : Option<String> = row.get(row.column_index("response_content_type")?)?;
+        let response_chunks : Option<String> = row.get(row.column_index("response_chunks")?)?;
+        let response_sse : bool = row.get(row.column_index("response_sse")?)?;
+        let response_cors : Option<String> = row.get(row.column_index("response_cors")?)?;
+
+        Ok(MockingRule {
+            id,
+            request_method,
+            request_host,
+            request_url,
+            request_query,
+            request_data,
+            request_form,
+            request_files,
+            request_xpath,
+            request_namespaces,
+            request_soap_action,
+            response_status_code,
+            response_data,
+            response_content_type,
+            response_chunks,
+            response_sse,
+            response_cors,
+        })
+    }
+
+    pub fn all() -> Result<Vec<MockingRule>, DbError>{
+        //! Returns all `MockingRule` records stored in database.
+        let conn = settings::DB.lock().unwrap();
+        let mut stmt = conn.prepare(
+            &format!("SELECT * FROM {} ORDER BY id;", Self::TABLE_NAME)
+        )?;
+
+        let results = stmt.query_map(NO_PARAMS, Self::from_row)?;
+
+        let mut output : Vec<MockingRule> = vec![];
+        for mocking_rule in results{
+            output.push(
+                mocking_rule?
+            );
+        }
+        Ok(output)
+    }
+
     pub fn find(
         request_url: &Option<String>,
         request_query: &Option<String>,
         request_method : &Option<Method>,
-        request_data: &Option<String>
-    ) -> Result<Vec<MockingRule>, String>{
-        //! Static function for finding a record in Database.
+        request_data: &Option<String>,
+        request_host: &Option<String>
+    ) -> Result<Vec<MockingRule>, DbError>{
+        //! Static function for finding a record in Database. Body matchers
+        //! other than `request_data` are not compared, see `same_body_matchers`.
         // TODO: Make this more configurable!!!
 
         let conn = settings::DB.lock().unwrap();
@@ -137,63 +400,145 @@ impl MockingRule {
         
         let mut stmt = conn.prepare(
             &format!(
-                "SELECT * FROM {} WHERE request_url {} ? AND request_query {} ? AND request_method {} ? AND request_data {} ?;",
+                "SELECT * FROM {} WHERE request_url {} ? AND request_query {} ? AND request_method {} ? AND request_data {} ? AND request_host {} ?;",
                 Self::TABLE_NAME,
                 if request_url.is_some(){ "=" } else {"is"},
                 if request_query.is_some(){ "=" } else {"is"},
                 if prepared_request_method.is_some(){ "=" } else {"is"},
                 if request_data.is_some(){ "=" } else {"is"},
+                if request_host.is_some(){ "=" } else {"is"},
             )
-        ).map_err(|e|e.to_string())?;
+        )?;
         
         let results = stmt.query_map(
             params![
                 request_url,
                 request_query,
                 prepared_request_method,
-                request_data
-            ], |row| {
-
-                let id : Option<i64> = row.get(row.column_index("id")?)?;
-                let request_method_raw : String = row.get(row.column_index("request_method")?)?;
-                let request_method : Method = Method::from_str(&request_method_raw).map_err(
-                    |e| FromSqlError::Other(Box::new(e))
-                )?;
-                
-                let request_url : String = row.get(row.column_index("request_url")?)?;
-                let request_query : Option<String> = row.get(row.column_index("request_query")?)?;
-                let request_data : Option<String> = row.get(row.column_index("request_data")?)?;
-
-                let response_status_code : i64 = row.get(row.column_index("response_status_code")?)?;
-                let response_data : Option<String> = row.get(row.column_index("response_data")?)?;
-
-                let output = Ok(MockingRule {
-                    id: id,
-                    request_method: request_method,
-                    request_url: request_url,
-                    request_query: request_query,
-                    request_data: request_data,
-                    response_status_code: response_status_code,
-                    response_data: response_data,
-                });
-                return output;
-            }
-        ).map_err(|e|e.to_string())?;
+                request_data,
+                request_host
+            ], Self::from_row
+        )?;
         
         let mut output : Vec<MockingRule> = vec![];
         for mocking_rule in results{
             output.push(
-                mocking_rule.map_err(|e|e.to_string())?
+                mocking_rule?
             );
         }
         
         return Ok(output);
     }
     
-    pub fn delete(&mut self) -> Result<(), String> {
+    pub fn find_matching(
+        request_url: &str,
+        request_query: &Option<String>,
+        request_method : &Method,
+        request_data: &Option<String>,
+        request_host: &Option<String>
+    ) -> Result<Vec<MockingRule>, DbError>{
+        //! Finds rules matching incoming request. Unlike `find`, rules without
+        //! `request_host` match any host. Rules bound to host are returned first,
+        //! rules for `ANY` method are returned after ones for specific method.
+        //! Rules with form, XPath or SOAP action matchers are candidates only, those are matched by caller.
+        let conn = settings::DB.lock().unwrap();
+
+        let mut stmt = conn.prepare(
+            &format!(
+                "SELECT * FROM {} WHERE request_url = ? AND request_query {} ? AND request_method IN (?, ?)
+                    AND (request_data {} ? OR (request_data IS NULL AND (
+                        request_form IS NOT NULL OR request_files IS NOT NULL
+                        OR request_xpath IS NOT NULL OR request_soap_action IS NOT NULL
+                    )))
+                    AND (request_host IS NULL OR request_host = ?)
+                ORDER BY request_host IS NULL, request_method = ?, id;",
+                Self::TABLE_NAME,
+                if request_query.is_some(){ "=" } else {"is"},
+                if request_data.is_some(){ "=" } else {"is"},
+            )
+        )?;
+
+        let results = stmt.query_map(
+            params![
+                request_url,
+                request_query,
+                request_method.as_str(),
+                ANY_METHOD,
+                request_data,
+                request_host,
+                ANY_METHOD
+            ], Self::from_row
+        )?;
+
+        let mut output : Vec<MockingRule> = vec![];
+        for mocking_rule in results{
+            output.push(
+                mocking_rule?
+            );
+        }
+        Ok(output)
+    }
+
+    pub fn requires_body(
+        request_url: &str,
+        request_query: &Option<String>,
+        request_method : &Method,
+        request_host: &Option<String>
+    ) -> Result<bool, DbError>{
+        //! Checks whether any rule, which could match the request, matches on
+        //! request body. Only then the body has to be buffered before matching.
+        let conn = settings::DB.lock().unwrap();
+
+        conn.query_row(
+            &format!(
+                "SELECT COUNT(*) FROM {} WHERE request_url = ? AND request_query {} ? AND request_method IN (?, ?)
+                    AND (request_data IS NOT NULL OR request_form IS NOT NULL OR request_files IS NOT NULL OR request_xpath IS NOT NULL)
+                    AND (request_host IS NULL OR request_host = ?);",
+                Self::TABLE_NAME,
+                if request_query.is_some(){ "=" } else {"is"},
+            ),
+            params![
+                request_url,
+                request_query,
+                request_method.as_str(),
+                ANY_METHOD,
+                request_host
+            ],
+            |row| row.get::<_, i64>(0)
+        ).map(|count| count > 0).map_err(DbError::from)
+    }
+
+    pub fn find_cors(
+        request_url: &str,
+        request_method : &Method,
+        request_host: &Option<String>
+    ) -> Result<Option<String>, DbError>{
+        //! Finds CORS policy of rule, which would handle request preflighted for `request_method`.
+        //! Rules bound to host take precedence.
+        let conn = settings::DB.lock().unwrap();
+
+        conn.query_row(
+            &format!(
+                "SELECT response_cors FROM {} WHERE request_url = ? AND request_method IN (?, ?) AND response_cors IS NOT NULL
+                    AND (request_host IS NULL OR request_host = ?)
+                ORDER BY request_host IS NULL, request_method = ?, id LIMIT 1;",
+                Self::TABLE_NAME,
+            ),
+            params![
+                request_url,
+                request_method.as_str(),
+                ANY_METHOD,
+                request_host,
+                ANY_METHOD
+            ],
+            |row| row.get::<_, String>(0)
+        ).optional().map_err(DbError::from)
+    }
+
+    pub fn delete(&mut self) -> Result<(), DbError> {
         //! Deletes `MockingRule` from a database.
         if self.id.is_none() {
-            return Err("Cannot delete MockingRule which does not exist in database.".to_owned());
+            return Err(DbError::Invalid("Cannot delete MockingRule which does not exist in database.".to_owned()));
         }
         
         let conn = settings::DB.lock().unwrap();
@@ -204,25 +549,837 @@ impl MockingRule {
         ) {
             Ok(query_result_count) => {
                 if query_result_count == 0{
-                    return Err("Database failed to perform delete".to_owned());
+                    return Err(DbError::NotFound("Record has already been deleted".to_owned()));
                 }
                 Ok(())
             },
-            Err(e) => Err(e.to_string())
+            Err(e) => Err(e.into())
         }
     }
+    pub fn same_body_matchers(&self, other: &MockingRule) -> bool {
+        //! Checks if rules match on the same form fields, files, XPath expressions and SOAP action.
+        self.request_form == other.request_form
+            && self.request_files == other.request_files
+            && self.request_xpath == other.request_xpath
+            && self.request_namespaces == other.request_namespaces
+            && self.request_soap_action == other.request_soap_action
+    }
+
     pub fn display_id(&self) -> String{
         //! Displays `id` as string
-        match self.id {
-            Some(id) => id.to_string(),
-            None => "<None>".to_string()
-        }
+        display_id(self.id)
     }
 }
 
-pub fn initialize_db() -> Result<(), String>{
+pub fn initialize_db() -> Result<(), DbError>{
     //! Initializes database.
     MockingRule::create_db_table()?;
+    JournalEntry::create_db_table()?;
+    RoutingRule::create_db_table()?;
+    RewriteRule::create_db_table()?;
+    PatchRule::create_db_table()?;
+    WebSocketFrame::create_db_table()?;
+    WebSocketRule::create_db_table()?;
+    GrpcRule::create_db_table()?;
+    GraphqlRule::create_db_table()?;
     println!("[+] Initializing in-memory sqlite");
     Ok(())
 }
+
+/// Simple ORM for journal of requests received by gateway
+#[derive(Debug)]
+pub struct JournalEntry {
+    pub id: Option<i64>,
+    pub started_at: i64,
+    pub duration_ms: i64,
+    pub request_method: Method,
+    pub request_host: Option<String>,
+    pub request_url: String,
+    pub request_query: Option<String>,
+    pub request_headers: String,
+    pub request_data: Option<String>,
+    pub response_status_code: i64,
+    pub response_headers: String,
+    pub response_data: Option<String>,
+    pub matched_rule_id: Option<i64>,
+}
+
+impl JournalEntry {
+    /// Defines name of db table for `JournalEntry` model
+    const TABLE_NAME: &'static str = "journal_entries";
+
+    pub fn create_db_table() -> Result<(), DbError>{
+        //! Creates a new DB for model
+        let conn = settings::DB.lock().unwrap();
+        conn.execute(
+            &format!(
+            "CREATE TABLE IF NOT EXISTS {} (
+                id                      INTEGER PRIMARY KEY,
+                started_at              INTEGER NOT NULL,
+                duration_ms             INTEGER NOT NULL,
+                request_method          TEXT NOT NULL,
+                request_host            TEXT,
+                request_url             TEXT NOT NULL,
+                request_query           TEXT,
+                request_headers         TEXT NOT NULL,
+                request_data            TEXT,
+                response_status_code    INTEGER NOT NULL,
+                response_headers        TEXT NOT NULL,
+                response_data           TEXT,
+                matched_rule_id         INTEGER
+            )", Self::TABLE_NAME),
+            params![],
+        )?;
+        Ok(())
+    }
+
+    pub fn create(&mut self) -> Result<(), DbError> {
+        //! Saves instantiated, nonexistent `JournalEntry` record.
+        if self.id.is_some() {
+            return Err(DbError::Invalid("JournalEntry already exists. Cannot create records with already existing ID".to_owned()));
+        }
+
+        let conn = settings::DB.lock().unwrap();
+        let query_result_count = conn.execute(
+            &format!("
+                INSERT INTO {} (
+                    started_at,
+                    duration_ms,
+                    request_method,
+                    request_host,
+                    request_url,
+                    request_query,
+                    request_headers,
+                    request_data,
+                    response_status_code,
+                    response_headers,
+                    response_data,
+                    matched_rule_id)
+                VALUES
+                    (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                Self::TABLE_NAME
+            ), params![
+                self.started_at,
+                self.duration_ms,
+                self.request_method.as_str(),
+                self.request_host,
+                self.request_url,
+                self.request_query,
+                self.request_headers,
+                self.request_data,
+                self.response_status_code,
+                self.response_headers,
+                self.response_data,
+                self.matched_rule_id
+            ],
+        )?;
+
+        if query_result_count == 0 {
+            return Err(DbError::Invalid("Database failed to perform insert".to_owned()));
+        }
+        self.id = Some(conn.last_insert_rowid());
+        Ok(())
+    }
+
+    fn from_row(row: &Row) -> rusqlite::Result<JournalEntry> {
+        //! Maps a database row onto `JournalEntry`.
+        let request_method_raw : String = row.get(row.column_index("request_method")?)?;
+        let request_method : Method = Method::from_str(&request_method_raw).map_err(
+            |e| FromSqlError::Other(Box::new(e))
+        )?;
+
+        Ok(JournalEntry {
+            id: row.get(row.column_index("id")?)?,
+            started_at: row.get(row.column_index("started_at")?)?,
+            duration_ms: row.get(row.column_index("duration_ms")?)?,
+            request_method,
+            request_host: row.get(row.column_index("request_host")?)?,
+            request_url: row.get(row.column_index("request_url")?)?,
+            request_query: row.get(row.column_index("request_query")?)?,
+            request_headers: row.get(row.column_index("request_headers")?)?,
+            request_data: row.get(row.column_index("request_data")?)?,
+            response_status_code: row.get(row.column_index("response_status_code")?)?,
+            response_headers: row.get(row.column_index("response_headers")?)?,
+            response_data: row.get(row.column_index("response_data")?)?,
+            matched_rule_id: row.get(row.column_index("matched_rule_id")?)?,
+        })
+    }
+
+    fn select(where_clause: &str, values: &[&dyn ToSql]) -> Result<Vec<JournalEntry>, DbError> {
+        //! Selects records matching `where_clause`, ordered as they were received.
+        let conn = settings::DB.lock().unwrap();
+        let mut stmt = conn.prepare(
+            &format!("SELECT * FROM {} WHERE {} ORDER BY id;", Self::TABLE_NAME, where_clause)
+        )?;
+
+        let results = stmt.query_map(values, Self::from_row)?;
+
+        let mut output : Vec<JournalEntry> = vec![];
+        for journal_entry in results{
+            output.push(
+                journal_entry?
+            );
+        }
+        Ok(output)
+    }
+
+    pub fn find(
+        request_url: &Option<String>,
+        request_query: &Option<String>,
+        request_method : &Option<Method>,
+        request_data: &Option<String>,
+        request_host: &Option<String>
+    ) -> Result<Vec<JournalEntry>, DbError>{
+        //! Finds journal entries, which would be matched by `MockingRule` with same request fields.
+        //! Just like for rules, if `request_host` is not provided, entries with any host are found.
+        //! `ANY` method matches entries with any method.
+        let prepared_request_method : Option<String> = request_method.as_ref().map(
+            |method| method.as_str().to_owned()
+        );
+
+        Self::select(
+            &format!(
+                "request_url {} ? AND request_query {} ? AND (request_method {} ? OR ? = ?) AND request_data {} ?
+                    AND (? IS NULL OR request_host = ?)",
+                if request_url.is_some(){ "=" } else {"is"},
+                if request_query.is_some(){ "=" } else {"is"},
+                if prepared_request_method.is_some(){ "=" } else {"is"},
+                if request_data.is_some(){ "=" } else {"is"},
+            ),
+            params![
+                request_url,
+                request_query,
+                prepared_request_method,
+                prepared_request_method,
+                ANY_METHOD,
+                request_data,
+                request_host,
+                request_host
+            ]
+        )
+    }
+
+    pub fn all() -> Result<Vec<JournalEntry>, DbError>{
+        //! Returns all `JournalEntry` records stored in database.
+        Self::select("1 = 1", NO_PARAMS)
+    }
+
+    pub fn find_unmatched() -> Result<Vec<JournalEntry>, DbError>{
+        //! Finds journal entries, which have not been mocked by any `MockingRule`.
+        Self::select("matched_rule_id IS NULL", NO_PARAMS)
+    }
+
+    pub fn find_by_rule_id(rule_id: i64) -> Result<Vec<JournalEntry>, DbError>{
+        //! Finds journal entries, which have been mocked by `MockingRule` with `rule_id`.
+        Self::select("matched_rule_id = ?", params![rule_id])
+    }
+
+    pub fn as_json(&self) -> JsonValue {
+        //! Serializes `JournalEntry` into `serde_json::Value`.
+        json!({
+            "id": self.id,
+            "started_at": self.started_at,
+            "duration_ms": self.duration_ms,
+            "mocked": self.matched_rule_id.is_some(),
+            "matched_rule_id": self.matched_rule_id,
+            "request": {
+                "method": self.request_method.as_str(),
+                "host": self.request_host,
+                "url": self.request_url,
+                "query": self.request_query,
+                "headers": serde_json::from_str::<JsonValue>(&self.request_headers).unwrap_or(JsonValue::Null),
+                "data": self.request_data,
+            },
+            "response": {
+                "status_code": self.response_status_code,
+                "headers": serde_json::from_str::<JsonValue>(&self.response_headers).unwrap_or(JsonValue::Null),
+                "data": self.response_data,
+            },
+        })
+    }
+}
+
+/// Simple ORM for WebSocket frames relayed in connection recorded by journal
+#[derive(Debug)]
+pub struct WebSocketFrame {
+    pub id: Option<i64>,
+    pub journal_entry_id: i64,
+    pub sent_at: i64,
+    /// `send` for frames sent by client, `receive` for frames sent to client.
+    pub direction: String,
+    pub opcode: i64,
+    /// Text of frame. Binary frames are base64 encoded.
+    pub data: String,
+}
+
+impl Record for WebSocketFrame {
+    const TABLE_NAME: &'static str = "websocket_frames";
+    const DISPLAY_NAME: &'static str = "WebSocket frame";
+    const COLUMNS: &'static [(&'static str, &'static str)] = &[
+        ("journal_entry_id", "INTEGER NOT NULL"),
+        ("sent_at", "INTEGER NOT NULL"),
+        ("direction", "TEXT NOT NULL"),
+        ("opcode", "INTEGER NOT NULL"),
+        ("data", "TEXT NOT NULL"),
+    ];
+
+    fn id(&self) -> Option<i64> { self.id }
+
+    fn set_id(&mut self, id: i64) { self.id = Some(id) }
+
+    fn values(&self) -> Vec<Box<dyn ToSql + '_>> {
+        vec![
+            Box::new(self.journal_entry_id),
+            Box::new(&self.sent_at),
+            Box::new(&self.direction),
+            Box::new(self.opcode),
+            Box::new(&self.data),
+        ]
+    }
+
+    fn from_row(row: &Row) -> rusqlite::Result<WebSocketFrame> {
+        //! Maps a database row onto `WebSocketFrame`.
+        Ok(WebSocketFrame {
+            id: row.get(row.column_index("id")?)?,
+            journal_entry_id: row.get(row.column_index("journal_entry_id")?)?,
+            sent_at: row.get(row.column_index("sent_at")?)?,
+            direction: row.get(row.column_index("direction")?)?,
+            opcode: row.get(row.column_index("opcode")?)?,
+            data: row.get(row.column_index("data")?)?,
+        })
+    }
+
+    fn as_json(&self) -> JsonValue {
+        //! Serializes `WebSocketFrame` into `serde_json::Value`.
+        json!({
+            "id": self.id,
+            "journal_entry_id": self.journal_entry_id,
+            "sent_at": self.sent_at,
+            "direction": self.direction,
+            "opcode": self.opcode,
+            "data": self.data,
+        })
+    }
+}
+
+impl WebSocketFrame {
+    pub fn find_by_journal_entry_id(journal_entry_id: i64) -> Result<Vec<WebSocketFrame>, DbError>{
+        //! Returns frames of connection recorded by journal entry, in order they were relayed.
+        Self::select("journal_entry_id = ?", params![journal_entry_id])
+    }
+}
+
+/// Simple ORM for rules mocking WebSocket endpoints with scripted conversation
+#[derive(Debug)]
+pub struct WebSocketRule {
+    pub id: Option<i64>,
+    pub request_path: String,
+    pub request_host: Option<String>,
+    /// JSON array of messages sent right after connection is accepted.
+    pub on_connect: String,
+    /// JSON array of `{"pattern": .., "message": ..}` objects. Message of first
+    /// pattern matching received text message is sent back.
+    pub replies: String,
+    pub periodic_message: Option<String>,
+    pub periodic_interval_ms: Option<i64>,
+    pub close_after_ms: Option<i64>,
+    pub close_code: i64,
+    pub close_reason: Option<String>,
+}
+
+impl Record for WebSocketRule {
+    const TABLE_NAME: &'static str = "websocket_rules";
+    const DISPLAY_NAME: &'static str = "WebSocket rule";
+    const COLUMNS: &'static [(&'static str, &'static str)] = &[
+        ("request_path", "TEXT NOT NULL"),
+        ("request_host", "TEXT"),
+        ("on_connect", "TEXT NOT NULL"),
+        ("replies", "TEXT NOT NULL"),
+        ("periodic_message", "TEXT"),
+        ("periodic_interval_ms", "INTEGER"),
+        ("close_after_ms", "INTEGER"),
+        ("close_code", "INTEGER NOT NULL"),
+        ("close_reason", "TEXT"),
+    ];
+    /// Rules bound to host come first.
+    const ORDER_BY: &'static str = "request_host IS NULL, id";
+
+    fn id(&self) -> Option<i64> { self.id }
+
+    fn set_id(&mut self, id: i64) { self.id = Some(id) }
+
+    fn values(&self) -> Vec<Box<dyn ToSql + '_>> {
+        vec![
+            Box::new(&self.request_path),
+            Box::new(&self.request_host),
+            Box::new(&self.on_connect),
+            Box::new(&self.replies),
+            Box::new(&self.periodic_message),
+            Box::new(self.periodic_interval_ms),
+            Box::new(self.close_after_ms),
+            Box::new(self.close_code),
+            Box::new(&self.close_reason),
+        ]
+    }
+
+    fn check_duplicate(&self) -> Result<(), DbError> {
+        //! Only one rule can mock endpoint of same path and host.
+        if !Self::select(
+            &format!("request_path = ? AND request_host {} ?", if self.request_host.is_some(){ "=" } else {"is"}),
+            params![self.request_path, self.request_host]
+        )?.is_empty() {
+            return Err(DbError::Duplicate("WebSocket rule on this endpoint already exists!".to_owned()));
+        }
+        Ok(())
+    }
+
+    fn from_row(row: &Row) -> rusqlite::Result<WebSocketRule> {
+        //! Maps a database row onto `WebSocketRule`.
+        Ok(WebSocketRule {
+            id: row.get(row.column_index("id")?)?,
+            request_path: row.get(row.column_index("request_path")?)?,
+            request_host: row.get(row.column_index("request_host")?)?,
+            on_connect: row.get(row.column_index("on_connect")?)?,
+            replies: row.get(row.column_index("replies")?)?,
+            periodic_message: row.get(row.column_index("periodic_message")?)?,
+            periodic_interval_ms: row.get(row.column_index("periodic_interval_ms")?)?,
+            close_after_ms: row.get(row.column_index("close_after_ms")?)?,
+            close_code: row.get(row.column_index("close_code")?)?,
+            close_reason: row.get(row.column_index("close_reason")?)?,
+        })
+    }
+
+    fn as_json(&self) -> JsonValue {
+        //! Serializes `WebSocketRule` into `serde_json::Value`.
+        let parse = |value: &str| serde_json::from_str(value).unwrap_or(JsonValue::Null);
+        json!({
+            "id": self.id,
+            "path": self.request_path,
+            "host": self.request_host,
+            "on_connect": parse(&self.on_connect),
+            "replies": parse(&self.replies),
+            "periodic_message": self.periodic_message.as_deref().map(parse),
+            "periodic_interval_ms": self.periodic_interval_ms,
+            "close_after_ms": self.close_after_ms,
+            "close_code": self.close_code,
+            "close_reason": self.close_reason,
+        })
+    }
+}
+
+impl WebSocketRule {
+    pub fn find_matching(request_path: &str, request_host: &Option<String>) -> Result<Option<WebSocketRule>, DbError>{
+        //! Finds rule mocking WebSocket endpoint. Rules without `request_host` match any host.
+        Ok(
+            Self::select(
+                "request_path = ? AND (request_host IS NULL OR request_host = ?)",
+                params![request_path, request_host]
+            )?.into_iter().next()
+        )
+    }
+}
+
+/// Simple ORM for rules mocking gRPC methods
+#[derive(Debug)]
+pub struct GrpcRule {
+    pub id: Option<i64>,
+    /// Fully qualified name of service, e.g. `package.Service`.
+    pub service: String,
+    pub method: String,
+    /// JSON array of `{"message": .., "delay_ms": ..}` objects. Messages are encoded
+    /// using output type of method. Server-streaming methods can respond with more messages.
+    pub response_messages: String,
+    pub status_code: i64,
+    pub status_message: Option<String>,
+}
+
+impl Record for GrpcRule {
+    const TABLE_NAME: &'static str = "grpc_rules";
+    const DISPLAY_NAME: &'static str = "gRPC rule";
+    const COLUMNS: &'static [(&'static str, &'static str)] = &[
+        ("service", "TEXT NOT NULL"),
+        ("method", "TEXT NOT NULL"),
+        ("response_messages", "TEXT NOT NULL"),
+        ("status_code", "INTEGER NOT NULL"),
+        ("status_message", "TEXT"),
+    ];
+
+    fn id(&self) -> Option<i64> { self.id }
+
+    fn set_id(&mut self, id: i64) { self.id = Some(id) }
+
+    fn values(&self) -> Vec<Box<dyn ToSql + '_>> {
+        vec![
+            Box::new(&self.service),
+            Box::new(&self.method),
+            Box::new(&self.response_messages),
+            Box::new(self.status_code),
+            Box::new(&self.status_message),
+        ]
+    }
+
+    fn check_duplicate(&self) -> Result<(), DbError> {
+        //! Only one rule can mock each method.
+        if Self::find_matching(&self.service, &self.method)?.is_some() {
+            return Err(DbError::Duplicate("gRPC rule for this method already exists!".to_owned()));
+        }
+        Ok(())
+    }
+
+    fn from_row(row: &Row) -> rusqlite::Result<GrpcRule> {
+        //! Maps a database row onto `GrpcRule`.
+        Ok(GrpcRule {
+            id: row.get(row.column_index("id")?)?,
+            service: row.get(row.column_index("service")?)?,
+            method: row.get(row.column_index("method")?)?,
+            response_messages: row.get(row.column_index("response_messages")?)?,
+            status_code: row.get(row.column_index("status_code")?)?,
+            status_message: row.get(row.column_index("status_message")?)?,
+        })
+    }
+
+    fn as_json(&self) -> JsonValue {
+        //! Serializes `GrpcRule` into `serde_json::Value`.
+        json!({
+            "id": self.id,
+            "service": self.service,
+            "method": self.method,
+            "responses": serde_json::from_str::<JsonValue>(&self.response_messages).unwrap_or(JsonValue::Null),
+            "status_code": self.status_code,
+            "status_message": self.status_message,
+        })
+    }
+}
+
+impl GrpcRule {
+    pub fn find_matching(service: &str, method: &str) -> Result<Option<GrpcRule>, DbError>{
+        //! Finds rule mocking `method` of `service`.
+        Ok(Self::select("service = ? AND method = ?", params![service, method])?.pop())
+    }
+}
+
+/// Simple ORM for rules mocking GraphQL operations
+#[derive(Debug)]
+pub struct GraphqlRule {
+    pub id: Option<i64>,
+    /// Path of GraphQL endpoint, e.g. `/graphql`.
+    pub path: String,
+    pub operation_name: Option<String>,
+    /// `query`, `mutation` or `subscription`.
+    pub operation_type: Option<String>,
+    /// JSON array of root field names, which operation must select.
+    pub root_fields: String,
+    /// JSON object, which must be contained in variables of operation.
+    pub variables: Option<String>,
+    pub response_status_code: i64,
+    pub response_data: Option<String>,
+    pub response_errors: Option<String>,
+}
+
+impl Record for GraphqlRule {
+    const TABLE_NAME: &'static str = "graphql_rules";
+    const DISPLAY_NAME: &'static str = "GraphQL rule";
+    const COLUMNS: &'static [(&'static str, &'static str)] = &[
+        ("path", "TEXT NOT NULL"),
+        ("operation_name", "TEXT"),
+        ("operation_type", "TEXT"),
+        ("root_fields", "TEXT NOT NULL"),
+        ("variables", "TEXT"),
+        ("response_status_code", "INTEGER NOT NULL"),
+        ("response_data", "TEXT"),
+        ("response_errors", "TEXT"),
+    ];
+
+    fn id(&self) -> Option<i64> { self.id }
+
+    fn set_id(&mut self, id: i64) { self.id = Some(id) }
+
+    fn values(&self) -> Vec<Box<dyn ToSql + '_>> {
+        vec![
+            Box::new(&self.path),
+            Box::new(&self.operation_name),
+            Box::new(&self.operation_type),
+            Box::new(&self.root_fields),
+            Box::new(&self.variables),
+            Box::new(self.response_status_code),
+            Box::new(&self.response_data),
+            Box::new(&self.response_errors),
+        ]
+    }
+
+    fn from_row(row: &Row) -> rusqlite::Result<GraphqlRule> {
+        //! Maps a database row onto `GraphqlRule`.
+        Ok(GraphqlRule {
+            id: row.get(row.column_index("id")?)?,
+            path: row.get(row.column_index("path")?)?,
+            operation_name: row.get(row.column_index("operation_name")?)?,
+            operation_type: row.get(row.column_index("operation_type")?)?,
+            root_fields: row.get(row.column_index("root_fields")?)?,
+            variables: row.get(row.column_index("variables")?)?,
+            response_status_code: row.get(row.column_index("response_status_code")?)?,
+            response_data: row.get(row.column_index("response_data")?)?,
+            response_errors: row.get(row.column_index("response_errors")?)?,
+        })
+    }
+
+    fn as_json(&self) -> JsonValue {
+        //! Serializes `GraphqlRule` into `serde_json::Value`.
+        let parse = |value: &str| serde_json::from_str(value).unwrap_or(JsonValue::Null);
+        json!({
+            "id": self.id,
+            "path": self.path,
+            "operation_name": self.operation_name,
+            "operation_type": self.operation_type,
+            "root_fields": parse(&self.root_fields),
+            "variables": self.variables.as_deref().map(parse),
+            "status_code": self.response_status_code,
+            "data": self.response_data.as_deref().map(parse),
+            "errors": self.response_errors.as_deref().map(parse),
+        })
+    }
+}
+
+impl GraphqlRule {
+    pub fn find_by_path(path: &str) -> Result<Vec<GraphqlRule>, DbError>{
+        //! Returns rules of GraphQL endpoint on `path` in order they are matched.
+        Self::select("path = ?", params![path])
+    }
+}
+
+/// Simple ORM for routing rules, which select target of proxied requests
+#[derive(Debug)]
+pub struct RoutingRule {
+    pub id: Option<i64>,
+    pub path_prefix: Option<String>,
+    pub host: Option<String>,
+    pub header_name: Option<String>,
+    pub header_value: Option<String>,
+    pub upstream: String,
+    pub strip_prefix: bool,
+    pub rewrite_prefix: Option<String>,
+}
+
+impl Record for RoutingRule {
+    const TABLE_NAME: &'static str = "routing_rules";
+    const DISPLAY_NAME: &'static str = "Route";
+    const COLUMNS: &'static [(&'static str, &'static str)] = &[
+        ("path_prefix", "TEXT"),
+        ("host", "TEXT"),
+        ("header_name", "TEXT"),
+        ("header_value", "TEXT"),
+        ("upstream", "TEXT NOT NULL"),
+        ("strip_prefix", "INTEGER NOT NULL"),
+        ("rewrite_prefix", "TEXT"),
+    ];
+
+    fn id(&self) -> Option<i64> { self.id }
+
+    fn set_id(&mut self, id: i64) { self.id = Some(id) }
+
+    fn values(&self) -> Vec<Box<dyn ToSql + '_>> {
+        vec![
+            Box::new(&self.path_prefix),
+            Box::new(&self.host),
+            Box::new(&self.header_name),
+            Box::new(&self.header_value),
+            Box::new(&self.upstream),
+            Box::new(self.strip_prefix),
+            Box::new(&self.rewrite_prefix),
+        ]
+    }
+
+    fn describe(&self) -> String {
+        //! Describes route with its upstream, e.g. `Route #1 to http://localhost:8080`.
+        format!("{} #{} to {}", Self::DISPLAY_NAME, display_id(self.id), self.upstream)
+    }
+
+    fn from_row(row: &Row) -> rusqlite::Result<RoutingRule> {
+        //! Maps a database row onto `RoutingRule`.
+        Ok(RoutingRule {
+            id: row.get(row.column_index("id")?)?,
+            path_prefix: row.get(row.column_index("path_prefix")?)?,
+            host: row.get(row.column_index("host")?)?,
+            header_name: row.get(row.column_index("header_name")?)?,
+            header_value: row.get(row.column_index("header_value")?)?,
+            upstream: row.get(row.column_index("upstream")?)?,
+            strip_prefix: row.get(row.column_index("strip_prefix")?)?,
+            rewrite_prefix: row.get(row.column_index("rewrite_prefix")?)?,
+        })
+    }
+
+    fn as_json(&self) -> JsonValue {
+        //! Serializes `RoutingRule` into `serde_json::Value`.
+        json!({
+            "id": self.id,
+            "path_prefix": self.path_prefix,
+            "host": self.host,
+            "header_name": self.header_name,
+            "header_value": self.header_value,
+            "upstream": self.upstream,
+            "strip_prefix": self.strip_prefix,
+            "rewrite_prefix": self.rewrite_prefix,
+        })
+    }
+}
+
+/// Simple ORM for rules rewriting proxied requests and responses
+#[derive(Debug)]
+pub struct RewriteRule {
+    pub id: Option<i64>,
+    pub method: Option<Method>,
+    pub host: Option<String>,
+    pub path_pattern: Option<String>,
+    pub path_replacement: Option<String>,
+    /// JSON object of headers added to (or replaced in) request sent to target.
+    pub request_headers_set: String,
+    /// JSON array of headers removed from request sent to target.
+    pub request_headers_remove: String,
+    /// JSON object of headers added to (or replaced in) response of target.
+    pub response_headers_set: String,
+    /// JSON array of headers removed from response of target.
+    pub response_headers_remove: String,
+}
+
+impl Record for RewriteRule {
+    const TABLE_NAME: &'static str = "rewrite_rules";
+    const DISPLAY_NAME: &'static str = "Rewrite rule";
+    const COLUMNS: &'static [(&'static str, &'static str)] = &[
+        ("method", "TEXT"),
+        ("host", "TEXT"),
+        ("path_pattern", "TEXT"),
+        ("path_replacement", "TEXT"),
+        ("request_headers_set", "TEXT NOT NULL"),
+        ("request_headers_remove", "TEXT NOT NULL"),
+        ("response_headers_set", "TEXT NOT NULL"),
+        ("response_headers_remove", "TEXT NOT NULL"),
+    ];
+
+    fn id(&self) -> Option<i64> { self.id }
+
+    fn set_id(&mut self, id: i64) { self.id = Some(id) }
+
+    fn values(&self) -> Vec<Box<dyn ToSql + '_>> {
+        vec![
+            Box::new(self.method.as_ref().map(|method| method.as_str())),
+            Box::new(&self.host),
+            Box::new(&self.path_pattern),
+            Box::new(&self.path_replacement),
+            Box::new(&self.request_headers_set),
+            Box::new(&self.request_headers_remove),
+            Box::new(&self.response_headers_set),
+            Box::new(&self.response_headers_remove),
+        ]
+    }
+
+    fn from_row(row: &Row) -> rusqlite::Result<RewriteRule> {
+        //! Maps a database row onto `RewriteRule`.
+        Ok(RewriteRule {
+            id: row.get(row.column_index("id")?)?,
+            method: method_from_row(row)?,
+            host: row.get(row.column_index("host")?)?,
+            path_pattern: row.get(row.column_index("path_pattern")?)?,
+            path_replacement: row.get(row.column_index("path_replacement")?)?,
+            request_headers_set: row.get(row.column_index("request_headers_set")?)?,
+            request_headers_remove: row.get(row.column_index("request_headers_remove")?)?,
+            response_headers_set: row.get(row.column_index("response_headers_set")?)?,
+            response_headers_remove: row.get(row.column_index("response_headers_remove")?)?,
+        })
+    }
+
+    fn as_json(&self) -> JsonValue {
+        //! Serializes `RewriteRule` into `serde_json::Value`.
+        let parse = |value: &str| serde_json::from_str(value).unwrap_or(JsonValue::Null);
+        json!({
+            "id": self.id,
+            "method": self.method.as_ref().map(|method| method.as_str()),
+            "host": self.host,
+            "path_pattern": self.path_pattern,
+            "path_replacement": self.path_replacement,
+            "request_headers_set": parse(&self.request_headers_set),
+            "request_headers_remove": parse(&self.request_headers_remove),
+            "response_headers_set": parse(&self.response_headers_set),
+            "response_headers_remove": parse(&self.response_headers_remove),
+        })
+    }
+}
+
+/// Simple ORM for rules modifying responses of target
+#[derive(Debug)]
+pub struct PatchRule {
+    pub id: Option<i64>,
+    pub method: Option<Method>,
+    pub host: Option<String>,
+    pub path_pattern: Option<String>,
+    /// JSON Patch (RFC 6902) applied to response body.
+    pub json_patch: Option<String>,
+    /// JSON merge patch (RFC 7396) applied to response body.
+    pub merge_patch: Option<String>,
+    pub status_code: Option<i64>,
+    /// JSON object of headers added to (or replaced in) response.
+    pub headers_set: String,
+    /// JSON array of headers removed from response.
+    pub headers_remove: String,
+}
+
+impl Record for PatchRule {
+    const TABLE_NAME: &'static str = "patch_rules";
+    const DISPLAY_NAME: &'static str = "Patch rule";
+    const COLUMNS: &'static [(&'static str, &'static str)] = &[
+        ("method", "TEXT"),
+        ("host", "TEXT"),
+        ("path_pattern", "TEXT"),
+        ("json_patch", "TEXT"),
+        ("merge_patch", "TEXT"),
+        ("status_code", "INTEGER"),
+        ("headers_set", "TEXT NOT NULL"),
+        ("headers_remove", "TEXT NOT NULL"),
+    ];
+
+    fn id(&self) -> Option<i64> { self.id }
+
+    fn set_id(&mut self, id: i64) { self.id = Some(id) }
+
+    fn values(&self) -> Vec<Box<dyn ToSql + '_>> {
+        vec![
+            Box::new(self.method.as_ref().map(|method| method.as_str())),
+            Box::new(&self.host),
+            Box::new(&self.path_pattern),
+            Box::new(&self.json_patch),
+            Box::new(&self.merge_patch),
+            Box::new(self.status_code),
+            Box::new(&self.headers_set),
+            Box::new(&self.headers_remove),
+        ]
+    }
+
+    fn from_row(row: &Row) -> rusqlite::Result<PatchRule> {
+        //! Maps a database row onto `PatchRule`.
+        Ok(PatchRule {
+            id: row.get(row.column_index("id")?)?,
+            method: method_from_row(row)?,
+            host: row.get(row.column_index("host")?)?,
+            path_pattern: row.get(row.column_index("path_pattern")?)?,
+            json_patch: row.get(row.column_index("json_patch")?)?,
+            merge_patch: row.get(row.column_index("merge_patch")?)?,
+            status_code: row.get(row.column_index("status_code")?)?,
+            headers_set: row.get(row.column_index("headers_set")?)?,
+            headers_remove: row.get(row.column_index("headers_remove")?)?,
+        })
+    }
+
+    fn as_json(&self) -> JsonValue {
+        //! Serializes `PatchRule` into `serde_json::Value`.
+        let parse = |value: &str| serde_json::from_str(value).unwrap_or(JsonValue::Null);
+        json!({
+            "id": self.id,
+            "method": self.method.as_ref().map(|method| method.as_str()),
+            "host": self.host,
+            "path_pattern": self.path_pattern,
+            "json_patch": self.json_patch.as_deref().map(parse),
+            "merge_patch": self.merge_patch.as_deref().map(parse),
+            "status_code": self.status_code,
+            "headers_set": parse(&self.headers_set),
+            "headers_remove": parse(&self.headers_remove),
+        })
+    }
+}
@@ -0,0 +1,226 @@
+use hyper::Method;
+use serde_json::{json, Value as JsonValue};
+
+use crate::db::{MockingRule, ANY_METHOD};
+use crate::errors::DbError;
+
+/// Maximum count of near-miss rules reported for single request.
+const MAX_NEAR_MISSES: usize = 3;
+
+/// Single request field, which differs from the one expected by `MockingRule`.
+#[derive(Debug)]
+pub struct FieldDiff {
+    pub field: &'static str,
+    pub expected: Option<String>,
+    pub actual: Option<String>,
+}
+
+/// `MockingRule`, which almost matched a request, with list of fields which differed.
+#[derive(Debug)]
+pub struct NearMiss {
+    pub rule_id: Option<i64>,
+    pub diffs: Vec<FieldDiff>,
+}
+
+impl NearMiss {
+    pub fn as_json(&self) -> JsonValue {
+        //! Serializes `NearMiss` into `serde_json::Value`.
+        let mut diff = serde_json::Map::new();
+        for field_diff in &self.diffs {
+            diff.insert(
+                field_diff.field.to_owned(),
+                json!({"expected": field_diff.expected, "actual": field_diff.actual})
+            );
+        }
+        json!({"rule_id": self.rule_id, "diff": diff})
+    }
+
+    pub fn describe(&self) -> String {
+        //! Describes differing fields in human readable form, suitable for log.
+        let display = |value: &Option<String>| match value {
+            Some(value) => format!("`{}`", value),
+            None => "<None>".to_owned()
+        };
+        self.diffs.iter().map(
+            |field_diff| format!(
+                "{} (expected {}, received {})",
+                field_diff.field,
+                display(&field_diff.expected),
+                display(&field_diff.actual)
+            )
+        ).collect::<Vec<String>>().join(", ")
+    }
+}
+
+fn diff_rule(
+    rule: &MockingRule,
+    request_url: &str,
+    request_query: &Option<String>,
+    request_method: &Method,
+    request_data: &Option<String>,
+    request_host: &Option<String>
+) -> (Vec<FieldDiff>, usize) {
+    //! Lists fields of request, which prevented `rule` from matching,
+    //! together with count of fields which have been compared.
+    let mut diffs : Vec<FieldDiff> = vec![];
+    let mut compared_fields = 4;
+
+    if let Some(rule_host) = &rule.request_host {
+        compared_fields += 1;
+        if Some(rule_host) != request_host.as_ref() {
+            diffs.push(FieldDiff {
+                field: "host",
+                expected: Some(rule_host.clone()),
+                actual: request_host.clone(),
+            });
+        }
+    }
+
+    if rule.request_method != request_method && rule.request_method.as_str() != ANY_METHOD {
+        diffs.push(FieldDiff {
+            field: "method",
+            expected: Some(rule.request_method.to_string()),
+            actual: Some(request_method.to_string()),
+        });
+    }
+    if rule.request_url != request_url {
+        diffs.push(FieldDiff {
+            field: "url",
+            expected: Some(rule.request_url.clone()),
+            actual: Some(request_url.to_owned()),
+        });
+    }
+    if rule.request_query != *request_query {
+        diffs.push(FieldDiff {
+            field: "query",
+            expected: rule.request_query.clone(),
+            actual: request_query.clone(),
+        });
+    }
+    // Bodies matched by form or XPath matchers are not compared as a whole.
+    let matches_parsed_body = rule.request_form.is_some() || rule.request_files.is_some() || rule.request_xpath.is_some();
+    if !matches_parsed_body && rule.request_data != *request_data {
+        diffs.push(FieldDiff {
+            field: "body",
+            expected: rule.request_data.clone(),
+            actual: request_data.clone(),
+        });
+    }
+    (diffs, compared_fields)
+}
+
+pub fn find_near_misses(
+    request_url: &str,
+    request_query: &Option<String>,
+    request_method: &Method,
+    request_data: &Option<String>,
+    request_host: &Option<String>
+) -> Result<Vec<NearMiss>, DbError> {
+    //! Finds rules closest to provided request. Closest rules are the ones with
+    //! the least differing fields. Rules which differ in every field are omitted.
+    let mut near_misses : Vec<NearMiss> = MockingRule::all()?.iter().filter_map(
+        |rule| {
+            let (diffs, compared_fields) = diff_rule(
+                rule, request_url, request_query, request_method, request_data, request_host
+            );
+            if diffs.len() < compared_fields {
+                Some(NearMiss { rule_id: rule.id, diffs })
+            } else {
+                None
+            }
+        }
+    ).collect();
+
+    near_misses.sort_by_key(|near_miss| near_miss.diffs.len());
+    near_misses.truncate(MAX_NEAR_MISSES);
+    Ok(near_misses)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mocking_rule() -> MockingRule {
+        MockingRule {
+            id: Some(1),
+            request_method: Method::POST,
+            request_host: None,
+            request_url: "/orders".to_owned(),
+            request_query: None,
+            request_data: Some("{\"id\":1}".to_owned()),
+            request_form: None,
+            request_files: None,
+            request_xpath: None,
+            request_namespaces: None,
+            request_soap_action: None,
+            response_status_code: 200,
+            response_data: None,
+            response_content_type: None,
+            response_chunks: None,
+            response_sse: false,
+            response_cors: None,
+        }
+    }
+
+    fn differing_fields(diffs: &[FieldDiff]) -> Vec<&'static str> {
+        diffs.iter().map(|field_diff| field_diff.field).collect()
+    }
+
+    #[test]
+    fn reports_no_diff_for_matching_request() {
+        let (diffs, compared_fields) = diff_rule(
+            &mocking_rule(), "/orders", &None, &Method::POST, &Some("{\"id\":1}".to_owned()), &None
+        );
+
+        assert!(diffs.is_empty());
+        assert_eq!(compared_fields, 4);
+    }
+
+    #[test]
+    fn reports_every_differing_field() {
+        let (diffs, compared_fields) = diff_rule(
+            &mocking_rule(), "/users", &Some("page=2".to_owned()), &Method::GET, &None, &None
+        );
+
+        assert_eq!(differing_fields(&diffs), vec!["method", "url", "query", "body"]);
+        assert_eq!(diffs[0].expected.as_deref(), Some("POST"));
+        assert_eq!(diffs[0].actual.as_deref(), Some("GET"));
+        assert_eq!(compared_fields, 4);
+    }
+
+    #[test]
+    fn compares_host_only_if_rule_has_one() {
+        let mut rule = mocking_rule();
+        rule.request_host = Some("api.local".to_owned());
+        let request_data = Some("{\"id\":1}".to_owned());
+
+        let (diffs, compared_fields) = diff_rule(&rule, "/orders", &None, &Method::POST, &request_data, &None);
+        assert_eq!(differing_fields(&diffs), vec!["host"]);
+        assert_eq!(compared_fields, 5);
+
+        let (diffs, _) = diff_rule(&rule, "/orders", &None, &Method::POST, &request_data, &Some("api.local".to_owned()));
+        assert!(diffs.is_empty());
+    }
+
+    #[test]
+    fn ignores_method_of_any_method_rule_and_body_of_parsed_body_rules() {
+        let mut rule = mocking_rule();
+        rule.request_method = Method::from_bytes(ANY_METHOD.as_bytes()).unwrap();
+        rule.request_form = Some("{\"title\":\"Report\"}".to_owned());
+
+        let (diffs, _) = diff_rule(&rule, "/orders", &None, &Method::DELETE, &Some("title=Other".to_owned()), &None);
+
+        assert!(diffs.is_empty());
+    }
+
+    #[test]
+    fn describes_diffs_for_log() {
+        let near_miss = NearMiss {
+            rule_id: Some(1),
+            diffs: vec![FieldDiff { field: "query", expected: Some("page=1".to_owned()), actual: None }],
+        };
+
+        assert_eq!(near_miss.describe(), "query (expected `page=1`, received <None>)");
+        assert_eq!(near_miss.as_json(), json!({"rule_id": 1, "diff": {"query": {"expected": "page=1", "actual": null}}}));
+    }
+}
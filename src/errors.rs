@@ -0,0 +1,180 @@
+use std::fmt;
+
+use hyper::http::StatusCode;
+use serde_json::json;
+
+use crate::body::BodyError;
+use crate::response::HTTPResponse;
+use crate::settings;
+
+/// Errors of database layer.
+#[derive(Debug)]
+pub enum DbError {
+    /// Record conflicts with already existing one.
+    Duplicate(String),
+    /// Record does not exist.
+    NotFound(String),
+    /// Record cannot be stored the way it was requested, e.g. it has been saved already.
+    Invalid(String),
+    /// SQLite failed to perform query.
+    Query(rusqlite::Error),
+}
+
+impl fmt::Display for DbError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DbError::Duplicate(message) | DbError::NotFound(message) | DbError::Invalid(message) => write!(f, "{}", message),
+            DbError::Query(error) => write!(f, "Database query failed: {}", error),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for DbError {
+    fn from(error: rusqlite::Error) -> Self {
+        DbError::Query(error)
+    }
+}
+
+impl From<DbError> for String {
+    fn from(error: DbError) -> Self {
+        error.to_string()
+    }
+}
+
+/// Validation error of single field of admin request.
+#[derive(Debug)]
+pub struct FieldError {
+    /// Name of field, e.g. `_rockery_request_url`.
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: &str, message: String) -> FieldError {
+        FieldError { field: field.to_owned(), message }
+    }
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Errors returned by admin API. Every error is returned in the same envelope
+/// `{"msg": ..., "error": ...}`, where `error` is stable code of error. Validation
+/// errors list failed fields in `fields` too.
+#[derive(Debug)]
+pub enum ApiError {
+    /// Request is not `application/json`.
+    UnsupportedMediaType,
+    /// Request cannot be handled the way it was sent, e.g. invalid WebSocket handshake.
+    BadRequest(String),
+    /// Request needs feature, which is disabled, e.g. CONNECT without forward proxy enabled.
+    Forbidden(String),
+    /// Request body cannot be read or parsed.
+    MalformedBody(String),
+    /// Fields of request failed validation.
+    Validation(Vec<FieldError>),
+    /// Record conflicts with already existing one.
+    Conflict(String),
+    NotFound(String),
+    /// Request body exceeds `MAX_BUFFERED_BODY`.
+    PayloadTooLarge(String),
+    /// Request cannot be passed to target.
+    BadGateway(String),
+    Internal(String),
+}
+
+impl ApiError {
+    pub fn code(&self) -> &'static str {
+        //! Returns machine readable code of error.
+        match self {
+            ApiError::UnsupportedMediaType => "unsupported_media_type",
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::Forbidden(_) => "forbidden",
+            ApiError::MalformedBody(_) => "malformed_body",
+            ApiError::Validation(_) => "validation_failed",
+            ApiError::Conflict(_) => "conflict",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::PayloadTooLarge(_) => "payload_too_large",
+            ApiError::BadGateway(_) => "bad_gateway",
+            ApiError::Internal(_) => "internal_error",
+        }
+    }
+
+    pub fn status_code(&self) -> StatusCode {
+        //! Returns HTTP status of error.
+        match self {
+            ApiError::UnsupportedMediaType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ApiError::BadRequest(_) | ApiError::MalformedBody(_) => StatusCode::BAD_REQUEST,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            ApiError::BadGateway(_) => StatusCode::BAD_GATEWAY,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        //! Describes error in human readable form.
+        match self {
+            ApiError::UnsupportedMediaType => "Request Content-Type header must be application/json".to_owned(),
+            ApiError::Validation(field_errors) if field_errors.len() == 1 => field_errors[0].message.clone(),
+            ApiError::Validation(field_errors) => format!("{} fields failed validation", field_errors.len()),
+            ApiError::BadRequest(message)
+            | ApiError::Forbidden(message)
+            | ApiError::MalformedBody(message)
+            | ApiError::Conflict(message)
+            | ApiError::NotFound(message)
+            | ApiError::PayloadTooLarge(message)
+            | ApiError::BadGateway(message)
+            | ApiError::Internal(message) => message.clone(),
+        }
+    }
+
+    pub fn as_http_response(&self) -> HTTPResponse {
+        //! Converts error into JSON response.
+        let mut body = json!({
+            "msg": self.describe(),
+            "error": self.code(),
+        });
+        if let ApiError::Validation(field_errors) = self {
+            body["fields"] = field_errors.iter().map(
+                |field_error| json!({"field": field_error.field, "message": field_error.message})
+            ).collect();
+        }
+        HTTPResponse{
+            status_code: self.status_code(),
+            body: body.to_string(),
+        }
+    }
+}
+
+impl From<DbError> for ApiError {
+    fn from(error: DbError) -> Self {
+        match error {
+            DbError::Duplicate(message) => ApiError::Conflict(message),
+            DbError::NotFound(message) => ApiError::NotFound(message),
+            error => ApiError::Internal(error.to_string()),
+        }
+    }
+}
+
+impl From<BodyError> for ApiError {
+    fn from(error: BodyError) -> Self {
+        let message = error.describe(*settings::MAX_BUFFERED_BODY);
+        match error {
+            BodyError::TooLarge => ApiError::PayloadTooLarge(message),
+            BodyError::Failed(_) => ApiError::MalformedBody(message),
+        }
+    }
+}
+
+impl From<ApiError> for HTTPResponse {
+    fn from(error: ApiError) -> Self {
+        error.as_http_response()
+    }
+}
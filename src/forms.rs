@@ -0,0 +1,277 @@
+use hyper::HeaderMap;
+use hyper::header;
+use serde_json::{Map as JsonMap, Value as JsonValue};
+
+use crate::errors::FieldError;
+
+/// Conditions, which can be put on uploaded file.
+const FILE_CONDITIONS: [&str; 4] = ["filename", "content_type", "min_size", "max_size"];
+
+/// Field of submitted form. Fields of url-encoded forms and text parts
+/// of multipart forms have no `filename`.
+#[derive(Debug)]
+pub struct FormPart {
+    pub name: String,
+    pub filename: Option<String>,
+    pub content_type: Option<String>,
+    pub data: Vec<u8>,
+}
+
+pub fn get_json_request_form(
+    parsed_json: &JsonValue,
+    field_name: &str,
+    error_messages: &mut Vec<FieldError>
+) -> Option<String>{
+    //! Extracts and validates object of form fields and their expected values.
+    match parsed_json.get(field_name) {
+        Some(JsonValue::Null) | None => None,
+        Some(JsonValue::Object(fields)) if fields.values().all(|value| value.is_string()) => {
+            Some(JsonValue::Object(fields.to_owned()).to_string())
+        },
+        Some(_) => {
+            error_messages.push(FieldError::new(field_name, format!("{} must be an object of field names and their string values", field_name)));
+            None
+        }
+    }
+}
+
+pub fn get_json_request_files(
+    parsed_json: &JsonValue,
+    field_name: &str,
+    error_messages: &mut Vec<FieldError>
+) -> Option<String>{
+    //! Extracts and validates object of file fields and conditions on uploaded
+    //! files - `filename`, `content_type`, `min_size` and `max_size` in bytes.
+    let files = match parsed_json.get(field_name) {
+        Some(JsonValue::Null) | None => return None,
+        Some(JsonValue::Object(files)) => files,
+        Some(_) => {
+            error_messages.push(FieldError::new(field_name, format!("{} must be an object of file fields and their conditions", field_name)));
+            return None;
+        }
+    };
+
+    for (name, conditions) in files.iter() {
+        let conditions = match conditions {
+            JsonValue::Object(conditions) => conditions,
+            _ => {
+                error_messages.push(FieldError::new(field_name, format!("{} must contain conditions of file {} as object", field_name, name)));
+                continue;
+            }
+        };
+        for (condition, value) in conditions.iter() {
+            let valid = match condition.as_str() {
+                "filename" | "content_type" => value.is_string(),
+                "min_size" | "max_size" => value.is_u64(),
+                _ => {
+                    error_messages.push(FieldError::new(field_name, format!(
+                        "{} contains unknown condition {}, expected one of {}", field_name, condition, FILE_CONDITIONS.join(", ")
+                    )));
+                    continue;
+                }
+            };
+            if !valid {
+                error_messages.push(FieldError::new(field_name, format!("{} contains invalid {} of file {}", field_name, condition, name)));
+            }
+        }
+    }
+    Some(JsonValue::Object(files.to_owned()).to_string())
+}
+
+fn find_subslice(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    //! Returns position of first occurrence of `needle` in `haystack`.
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+pub fn header_parameters(value: &str) -> Vec<(String, String)> {
+    //! Splits parameters of header value like `form-data; name="file"; filename="a.txt"`.
+    //! Parameter names are lowercased, quotes of values are removed.
+    value.split(';').skip(1).filter_map(|parameter| {
+        let (name, value) = parameter.split_once('=')?;
+        let value = value.trim();
+        let value = value.strip_prefix('"').and_then(|value| value.strip_suffix('"')).unwrap_or(value);
+        Some((name.trim().to_lowercase(), value.to_owned()))
+    }).collect()
+}
+
+fn parse_multipart(boundary: &str, body: &[u8]) -> Option<Vec<FormPart>> {
+    //! Parses `multipart/form-data` body. Returns `None` if body is malformed.
+    let delimiter = format!("--{}", boundary).into_bytes();
+    let mut parts = vec![];
+    let mut remaining = &body[find_subslice(body, &delimiter)? + delimiter.len()..];
+
+    loop {
+        if remaining.starts_with(b"--") {
+            return Some(parts);
+        }
+        remaining = remaining.strip_prefix(b"\r\n")?;
+        let headers_end = find_subslice(remaining, b"\r\n\r\n")?;
+        let headers = std::str::from_utf8(&remaining[..headers_end]).ok()?;
+        remaining = &remaining[headers_end + 4..];
+
+        let mut part = FormPart { name: String::new(), filename: None, content_type: None, data: vec![] };
+        for line in headers.split("\r\n") {
+            let (name, value) = line.split_once(':')?;
+            match name.trim().to_lowercase().as_str() {
+                "content-disposition" => for (parameter, value) in header_parameters(value) {
+                    match parameter.as_str() {
+                        "name" => part.name = value,
+                        "filename" => part.filename = Some(value),
+                        _ => (),
+                    }
+                },
+                "content-type" => part.content_type = Some(value.trim().to_owned()),
+                _ => (),
+            }
+        }
+
+        let mut part_end = b"\r\n".to_vec();
+        part_end.extend_from_slice(&delimiter);
+        let data_end = find_subslice(remaining, &part_end)?;
+        part.data = remaining[..data_end].to_vec();
+        parts.push(part);
+        remaining = &remaining[data_end + part_end.len()..];
+    }
+}
+
+pub fn parse_form_body(headers: &HeaderMap, body: &[u8]) -> Option<Vec<FormPart>> {
+    //! Parses url-encoded or multipart form, depending on `Content-Type` of request.
+    //! Returns `None` for other bodies.
+    let content_type = headers.get(header::CONTENT_TYPE)?.to_str().ok()?;
+    let media_type = content_type.split(';').next()?.trim().to_lowercase();
+    match media_type.as_str() {
+        "application/x-www-form-urlencoded" => Some(
+            form_urlencoded::parse(body).map(|(name, value)| FormPart {
+                name: name.into_owned(),
+                filename: None,
+                content_type: None,
+                data: value.into_owned().into_bytes(),
+            }).collect()
+        ),
+        "multipart/form-data" => {
+            let boundary = header_parameters(content_type).into_iter().find(|(name, _)| name == "boundary")?.1;
+            parse_multipart(&boundary, body)
+        },
+        _ => None,
+    }
+}
+
+fn file_matches(part: &FormPart, conditions: &JsonMap<String, JsonValue>) -> bool {
+    //! Checks if uploaded file satisfies all conditions.
+    let size = part.data.len() as u64;
+    conditions.iter().all(|(condition, value)| match condition.as_str() {
+        "filename" => part.filename.as_deref() == value.as_str(),
+        "content_type" => part.content_type.as_ref().is_some_and(
+            |content_type| Some(content_type.to_lowercase()) == value.as_str().map(|value| value.to_lowercase())
+        ),
+        "min_size" => value.as_u64().is_some_and(|min_size| size >= min_size),
+        "max_size" => value.as_u64().is_some_and(|max_size| size <= max_size),
+        _ => false,
+    })
+}
+
+pub fn form_matches(request_form: &Option<String>, request_files: &Option<String>, parts: &Option<Vec<FormPart>>) -> bool {
+    //! Checks if submitted form contains every expected field and file. Rules
+    //! without form matchers match any body.
+    if request_form.is_none() && request_files.is_none() {
+        return true;
+    }
+    let parts = match parts {
+        Some(parts) => parts,
+        None => return false,
+    };
+    let parse = |value: &Option<String>| match value.as_ref().map(|value| serde_json::from_str(value)) {
+        Some(Ok(JsonValue::Object(value))) => value,
+        _ => JsonMap::new(),
+    };
+
+    let fields_match = parse(request_form).iter().all(|(name, value)| parts.iter().any(
+        |part| part.filename.is_none() && part.name == *name && value.as_str().map(|value| value.as_bytes()) == Some(&part.data[..])
+    ));
+    let files_match = parse(request_files).iter().all(|(name, conditions)| parts.iter().any(
+        |part| part.filename.is_some() && part.name == *name && conditions.as_object().is_some_and(
+            |conditions| file_matches(part, conditions)
+        )
+    ));
+    fields_match && files_match
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOUNDARY: &str = "XyZ";
+
+    fn multipart_body() -> Vec<u8> {
+        concat!(
+            "--XyZ\r\n",
+            "Content-Disposition: form-data; name=\"title\"\r\n",
+            "\r\n",
+            "Report\r\n",
+            "--XyZ\r\n",
+            "Content-Disposition: form-data; name=\"file\"; filename=\"a.txt\"\r\n",
+            "Content-Type: text/plain\r\n",
+            "\r\n",
+            "hello\r\n",
+            "--XyZ--\r\n",
+        ).as_bytes().to_vec()
+    }
+
+    #[test]
+    fn parses_text_and_file_parts_of_multipart_body() {
+        let parts = parse_multipart(BOUNDARY, &multipart_body()).unwrap();
+
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].name, "title");
+        assert_eq!(parts[0].filename, None);
+        assert_eq!(parts[0].data, b"Report");
+        assert_eq!(parts[1].name, "file");
+        assert_eq!(parts[1].filename.as_deref(), Some("a.txt"));
+        assert_eq!(parts[1].content_type.as_deref(), Some("text/plain"));
+        assert_eq!(parts[1].data, b"hello");
+    }
+
+    #[test]
+    fn rejects_multipart_body_without_closing_delimiter() {
+        let body = b"--XyZ\r\nContent-Disposition: form-data; name=\"title\"\r\n\r\nReport";
+
+        assert!(parse_multipart(BOUNDARY, body).is_none());
+        assert!(parse_multipart("other", &multipart_body()).is_none());
+    }
+
+    #[test]
+    fn parses_form_body_by_content_type() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_TYPE, "application/x-www-form-urlencoded".parse().unwrap());
+        let parts = parse_form_body(&headers, b"a=1&b=two+words").unwrap();
+        assert_eq!(parts[1].name, "b");
+        assert_eq!(parts[1].data, b"two words");
+
+        headers.insert(header::CONTENT_TYPE, "multipart/form-data; boundary=\"XyZ\"".parse().unwrap());
+        assert_eq!(parse_form_body(&headers, &multipart_body()).unwrap().len(), 2);
+
+        headers.insert(header::CONTENT_TYPE, "application/json".parse().unwrap());
+        assert!(parse_form_body(&headers, b"{}").is_none());
+    }
+
+    #[test]
+    fn matches_expected_fields_and_files() {
+        let parts = parse_multipart(BOUNDARY, &multipart_body());
+        let form = Some(r#"{"title": "Report"}"#.to_owned());
+        let files = Some(r#"{"file": {"filename": "a.txt", "content_type": "TEXT/PLAIN", "min_size": 5, "max_size": 5}}"#.to_owned());
+
+        assert!(form_matches(&form, &files, &parts));
+        assert!(form_matches(&None, &None, &None));
+        assert!(!form_matches(&form, &None, &None));
+    }
+
+    #[test]
+    fn does_not_match_differing_fields_or_files() {
+        let parts = parse_multipart(BOUNDARY, &multipart_body());
+
+        assert!(!form_matches(&Some(r#"{"title": "Other"}"#.to_owned()), &None, &parts));
+        assert!(!form_matches(&Some(r#"{"file": "hello"}"#.to_owned()), &None, &parts));
+        assert!(!form_matches(&None, &Some(r#"{"title": {}}"#.to_owned()), &parts));
+        assert!(!form_matches(&None, &Some(r#"{"file": {"max_size": 4}}"#.to_owned()), &parts));
+    }
+}
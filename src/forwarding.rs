@@ -0,0 +1,213 @@
+use std::net::{IpAddr, SocketAddr};
+
+use core::str::FromStr;
+
+use hyper::{Body, HeaderMap, Method, Request, Version};
+use hyper::header::{self, HeaderName, HeaderValue};
+use hyper::http::uri::{Scheme, Uri};
+
+use crate::settings;
+
+/// Headers meaningful only for a single connection, which must not be forwarded (RFC 7230, section 6.1).
+const HOP_BY_HOP_HEADERS : [&str; 9] = [
+    "connection", "keep-alive", "proxy-authenticate", "proxy-authorization", "proxy-connection",
+    "te", "trailer", "transfer-encoding", "upgrade"
+];
+
+/// Connection, on which request was received. Stored in request extensions.
+#[derive(Clone, Copy)]
+pub struct ClientConnection {
+    pub address: SocketAddr,
+    /// Whether request was received over TLS, either on HTTPS gateway or in intercepted tunnel.
+    pub secure: bool,
+}
+
+impl ClientConnection {
+    fn proto(&self) -> &'static str {
+        if self.secure { "https" } else { "http" }
+    }
+}
+
+pub fn strip_hop_by_hop_headers(headers: &mut HeaderMap) {
+    //! Removes hop-by-hop headers, including those listed in `Connection` header.
+    let connection_options : Vec<HeaderName> = headers.get_all(header::CONNECTION).iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|option| HeaderName::from_bytes(option.trim().as_bytes()).ok())
+        .collect();
+
+    for name in connection_options {
+        headers.remove(name);
+    }
+    for name in HOP_BY_HOP_HEADERS.iter() {
+        headers.remove(*name);
+    }
+}
+
+pub fn accepts_trailers(headers: &HeaderMap) -> bool {
+    //! Checks if client accepts trailers via `TE: trailers`, as gRPC clients do.
+    //! Gateway passes trailers of target through, so it is sent to target as well.
+    headers.get_all(header::TE).iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|value| value.trim().eq_ignore_ascii_case("trailers"))
+}
+
+pub fn restore_host_header(headers: &mut HeaderMap, uri: &Uri) {
+    //! HTTP/2 requests carry host in `:authority` pseudo header instead of `Host` header.
+    //! It is restored, so target receives the same `Host` as from HTTP/1.1 clients.
+    if headers.contains_key(header::HOST) {
+        return;
+    }
+    if let Some(Ok(host)) = uri.authority().map(|authority| HeaderValue::from_str(authority.as_str())) {
+        headers.insert(header::HOST, host);
+    }
+}
+
+pub fn normalize_http2_request(mut req: Request<Body>) -> Request<Body> {
+    //! HTTP/2 requests received by gateway have absolute URI built from `:authority`, although they
+    //! are not meant for forward proxy. URI is reduced to origin-form and authority moves to `Host`.
+    if req.version() != Version::HTTP_2 || req.method() == Method::CONNECT {
+        return req;
+    }
+    let uri = req.uri().clone();
+    restore_host_header(req.headers_mut(), &uri);
+    if let Some(path_and_query) = uri.path_and_query() {
+        if let Ok(origin_form) = Uri::from_str(path_and_query.as_str()) {
+            *(req.uri_mut()) = origin_form;
+        }
+    }
+    req
+}
+
+pub fn is_addressed_to_gateway(uri: &Uri) -> bool {
+    //! Checks if request is meant for gateway itself - URI is in origin-form, or absolute URI
+    //! points to `ROCKERY_HOST` (or loopback) on `ROCKERY_PORT`. Requests for other hosts
+    //! reaching gateway as forward proxy must not be handled by admin API.
+    let host = match uri.host() {
+        Some(host) => host.trim_start_matches('[').trim_end_matches(']'),
+        None => return true,
+    };
+    let default_port = if uri.scheme() == Some(&Scheme::HTTPS) { 443 } else { 80 };
+    uri.port_u16().unwrap_or(default_port) == *settings::ROCKERY_PORT && (
+        host.eq_ignore_ascii_case(&settings::ROCKERY_HOST)
+        || host.eq_ignore_ascii_case("localhost")
+        || host.parse::<IpAddr>().is_ok_and(|address| address.is_loopback())
+    )
+}
+
+fn forwarded_node(address: &SocketAddr) -> String {
+    //! Formats client address as node of `Forwarded` header. IPv6 has to be quoted.
+    match address {
+        SocketAddr::V4(address) => address.ip().to_string(),
+        SocketAddr::V6(address) => format!("\"[{}]\"", address.ip()),
+    }
+}
+
+fn append_header(headers: &mut HeaderMap, name: &str, value: &str) {
+    //! Appends `value` to comma separated list in header `name`, or creates the header.
+    //! Repeated header lines are joined into a single list first.
+    let mut values : Vec<&str> = headers.get_all(name).iter().filter_map(|existing| existing.to_str().ok()).collect();
+    values.push(value);
+    if let Ok(value) = HeaderValue::from_str(&values.join(", ")) {
+        headers.insert(HeaderName::from_bytes(name.as_bytes()).unwrap(), value);
+    }
+}
+
+pub fn add_forwarding_headers(headers: &mut HeaderMap, uri: &Uri, client: Option<&ClientConnection>) {
+    //! Adds headers describing original request, which are enabled in settings. Client
+    //! address is appended to values set by previous proxies, while `X-Forwarded-Proto`
+    //! and `X-Forwarded-Host` set by previous proxies are kept untouched.
+    let original_host = headers.get(header::HOST).and_then(|host| host.to_str().ok()).map(|host| host.to_owned())
+        .or_else(|| uri.authority().map(|authority| authority.to_string()));
+
+    if let Some(client) = client {
+        if *settings::X_FORWARDED_FOR {
+            append_header(headers, "x-forwarded-for", &client.address.ip().to_string());
+        }
+        if *settings::X_FORWARDED_PROTO && !headers.contains_key("x-forwarded-proto") {
+            headers.insert("x-forwarded-proto", HeaderValue::from_static(client.proto()));
+        }
+    }
+
+    if *settings::X_FORWARDED_HOST && !headers.contains_key("x-forwarded-host") {
+        if let Some(Ok(host)) = original_host.as_ref().map(|host| HeaderValue::from_str(host)) {
+            headers.insert("x-forwarded-host", host);
+        }
+    }
+
+    if *settings::FORWARDED {
+        let mut element : Vec<String> = vec![];
+        if let Some(client) = client {
+            element.push(format!("for={}", forwarded_node(&client.address)));
+            element.push(format!("proto={}", client.proto()));
+        }
+        if let Some(host) = original_host {
+            element.push(format!("host=\"{}\"", host));
+        }
+        if !element.is_empty() {
+            append_header(headers, "forwarded", &element.join(";"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_hop_by_hop_and_connection_option_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONNECTION, HeaderValue::from_static("keep-alive, X-Trace"));
+        headers.append(header::CONNECTION, HeaderValue::from_static("x-debug"));
+        headers.insert("keep-alive", HeaderValue::from_static("timeout=5"));
+        headers.insert(header::TRANSFER_ENCODING, HeaderValue::from_static("chunked"));
+        headers.insert("x-trace", HeaderValue::from_static("1"));
+        headers.insert("x-debug", HeaderValue::from_static("1"));
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+
+        strip_hop_by_hop_headers(&mut headers);
+
+        assert_eq!(headers.len(), 1);
+        assert_eq!(headers[header::CONTENT_TYPE], "text/plain");
+    }
+
+    #[test]
+    fn creates_missing_header() {
+        let mut headers = HeaderMap::new();
+
+        append_header(&mut headers, "x-forwarded-for", "10.0.0.1");
+
+        assert_eq!(headers["x-forwarded-for"], "10.0.0.1");
+    }
+
+    #[test]
+    fn appends_to_every_line_of_repeated_header() {
+        let mut headers = HeaderMap::new();
+        headers.append("x-forwarded-for", HeaderValue::from_static("10.0.0.1, 10.0.0.2"));
+        headers.append("x-forwarded-for", HeaderValue::from_static("10.0.0.3"));
+
+        append_header(&mut headers, "x-forwarded-for", "127.0.0.1");
+
+        let values : Vec<&HeaderValue> = headers.get_all("x-forwarded-for").iter().collect();
+        assert_eq!(values, vec!["10.0.0.1, 10.0.0.2, 10.0.0.3, 127.0.0.1"]);
+    }
+
+    #[test]
+    fn recognizes_requests_meant_for_gateway() {
+        let port = *settings::ROCKERY_PORT;
+        let uri = |value: String| Uri::from_str(&value).unwrap();
+
+        assert!(is_addressed_to_gateway(&uri("/rockery-mock/routes".to_owned())));
+        assert!(is_addressed_to_gateway(&uri(format!("http://localhost:{}/rockery-mock/routes", port))));
+        assert!(is_addressed_to_gateway(&uri(format!("http://[::1]:{}/", port))));
+        assert!(!is_addressed_to_gateway(&uri(format!("http://api.example.com:{}/rockery-mock/routes", port))));
+        assert!(!is_addressed_to_gateway(&uri(format!("http://localhost:{}/rockery-mock/routes", port + 1))));
+    }
+
+    #[test]
+    fn quotes_ipv6_node_of_forwarded_header() {
+        assert_eq!(forwarded_node(&"10.0.0.1:4000".parse().unwrap()), "10.0.0.1");
+        assert_eq!(forwarded_node(&"[::1]:4000".parse().unwrap()), "\"[::1]\"");
+    }
+}
@@ -0,0 +1,357 @@
+use std::collections::HashMap;
+
+use graphql_parser::query::{parse_query, Definition, OperationDefinition, Selection, SelectionSet};
+use hyper::{Body, Method, Response};
+use hyper::header::{self, HeaderValue};
+use hyper::http::StatusCode;
+use serde_json::{json, Map as JsonMap, Value as JsonValue};
+
+use crate::db::GraphqlRule;
+use crate::errors::FieldError;
+use crate::utils::get_json_optional_string;
+
+/// Path of GraphQL endpoint, unless rule specifies other.
+const DEFAULT_GRAPHQL_PATH: &str = "/graphql";
+/// Operation types, which rules can match on.
+const OPERATION_TYPES: [&str; 3] = ["query", "mutation", "subscription"];
+
+/// Operation requested by client, reduced to parts rules match on.
+#[derive(Debug)]
+pub struct GraphqlOperation {
+    pub operation_name: Option<String>,
+    pub operation_type: String,
+    pub root_fields: Vec<String>,
+    pub variables: JsonValue,
+}
+
+pub fn graphql_rule_from_json(parsed_json: &JsonValue) -> Result<GraphqlRule, Vec<FieldError>>{
+    //! Creates `GraphqlRule` from provided `serde_json::Value` or returns error messages if validation fails.
+    //! Rule is not saved to db!
+    let mut error_messages : Vec<FieldError> = vec![];
+
+    let path = get_json_optional_string(parsed_json, "_rockery_graphql_path", &mut error_messages)
+        .unwrap_or_else(|| DEFAULT_GRAPHQL_PATH.to_owned());
+    if !path.starts_with('/') {
+        error_messages.push(FieldError::new("_rockery_graphql_path", "_rockery_graphql_path must start with /".to_owned()));
+    }
+
+    let operation_name = get_json_optional_string(parsed_json, "_rockery_graphql_operation_name", &mut error_messages);
+    let operation_type = get_json_optional_string(parsed_json, "_rockery_graphql_operation_type", &mut error_messages)
+        .map(|operation_type| operation_type.to_lowercase());
+    if let Some(operation_type) = &operation_type {
+        if !OPERATION_TYPES.contains(&operation_type.as_str()) {
+            error_messages.push(FieldError::new("_rockery_graphql_operation_type", format!("_rockery_graphql_operation_type must be one of {}", OPERATION_TYPES.join(", "))));
+        }
+    }
+
+    let root_fields = match parsed_json.get("_rockery_graphql_root_fields") {
+        Some(JsonValue::Null) | None => vec![],
+        Some(JsonValue::Array(root_fields)) if root_fields.iter().all(|root_field| root_field.is_string()) => root_fields.to_owned(),
+        Some(_) => {
+            error_messages.push(FieldError::new("_rockery_graphql_root_fields", "_rockery_graphql_root_fields must be an array of field names".to_owned()));
+            vec![]
+        }
+    };
+
+    let variables = match parsed_json.get("_rockery_graphql_variables") {
+        Some(JsonValue::Null) | None => None,
+        Some(variables @ JsonValue::Object(_)) => Some(variables.to_string()),
+        Some(_) => {
+            error_messages.push(FieldError::new("_rockery_graphql_variables", "_rockery_graphql_variables must be an object".to_owned()));
+            None
+        }
+    };
+
+    if operation_name.is_none() && operation_type.is_none() && root_fields.is_empty() && variables.is_none() {
+        error_messages.push(FieldError::new(
+            "_rockery_graphql_operation_name",
+            "At least one of _rockery_graphql_operation_name, _rockery_graphql_operation_type, \
+            _rockery_graphql_root_fields or _rockery_graphql_variables is required".to_owned()
+        ));
+    }
+
+    let response_data = match parsed_json.get("_rockery_graphql_data") {
+        None => None,
+        Some(data @ JsonValue::Null) | Some(data @ JsonValue::Object(_)) => Some(data.to_string()),
+        Some(_) => {
+            error_messages.push(FieldError::new("_rockery_graphql_data", "_rockery_graphql_data must be an object or null".to_owned()));
+            None
+        }
+    };
+    let response_errors = match parsed_json.get("_rockery_graphql_errors") {
+        Some(JsonValue::Null) | None => None,
+        Some(JsonValue::Array(errors)) if errors.iter().all(|error| error.get("message").is_some_and(|message| message.is_string())) => {
+            Some(JsonValue::Array(errors.to_owned()).to_string())
+        },
+        Some(_) => {
+            error_messages.push(FieldError::new("_rockery_graphql_errors", "_rockery_graphql_errors must be an array of objects with message".to_owned()));
+            None
+        }
+    };
+    if response_data.is_none() && response_errors.is_none() {
+        error_messages.push(FieldError::new("_rockery_graphql_data", "_rockery_graphql_data or _rockery_graphql_errors is required".to_owned()));
+    }
+
+    let response_status_code = match parsed_json.get("_rockery_graphql_status_code") {
+        Some(JsonValue::Null) | None => 200,
+        Some(status_code) => match status_code.as_u64().map(|status_code| StatusCode::from_u16(status_code as u16)) {
+            Some(Ok(status_code)) => status_code.as_u16() as i64,
+            _ => {
+                error_messages.push(FieldError::new("_rockery_graphql_status_code", "_rockery_graphql_status_code must be a valid HTTP status code".to_owned()));
+                200
+            }
+        }
+    };
+
+    if !error_messages.is_empty() {
+        return Err(error_messages);
+    }
+    Ok(
+        GraphqlRule {
+            id: None,
+            path,
+            operation_name,
+            operation_type,
+            root_fields: JsonValue::Array(root_fields).to_string(),
+            variables,
+            response_status_code,
+            response_data,
+            response_errors,
+        }
+    )
+}
+
+fn get_request_parameters(method: &Method, query: &Option<String>, body: Option<&[u8]>) -> Option<JsonMap<String, JsonValue>> {
+    //! Reads `query`, `operationName` and `variables` from query string of GET
+    //! requests, or from JSON body of POST requests. Body with `application/graphql`
+    //! content is the query itself. Batched operations are not matched.
+    if method == Method::GET {
+        let mut parameters = JsonMap::new();
+        for (name, value) in form_urlencoded::parse(query.as_ref()?.as_bytes()) {
+            let value = match name.as_ref() {
+                "variables" => serde_json::from_str(&value).ok()?,
+                _ => JsonValue::String(value.into_owned()),
+            };
+            parameters.insert(name.into_owned(), value);
+        }
+        return Some(parameters);
+    }
+
+    let body = body?;
+    match serde_json::from_slice(body) {
+        Ok(JsonValue::Object(parameters)) => Some(parameters),
+        Ok(_) => None,
+        Err(_) => {
+            let mut parameters = JsonMap::new();
+            parameters.insert("query".to_owned(), JsonValue::String(std::str::from_utf8(body).ok()?.to_owned()));
+            Some(parameters)
+        }
+    }
+}
+
+fn collect_root_fields<'a>(
+    selection_set: &SelectionSet<'a, &'a str>,
+    fragments: &HashMap<&'a str, &SelectionSet<'a, &'a str>>,
+    visited_fragments: &mut Vec<&'a str>,
+    root_fields: &mut Vec<String>
+) {
+    //! Collects names (not aliases) of fields selected on operation root, including
+    //! those selected through fragments.
+    for selection in selection_set.items.iter() {
+        match selection {
+            Selection::Field(field) => if !root_fields.iter().any(|root_field| root_field == field.name) {
+                root_fields.push(field.name.to_owned());
+            },
+            Selection::InlineFragment(fragment) => collect_root_fields(
+                &fragment.selection_set, fragments, visited_fragments, root_fields
+            ),
+            Selection::FragmentSpread(spread) => {
+                // Fragments cannot form cycles in valid documents, but malformed ones must not recurse forever.
+                if visited_fragments.contains(&spread.fragment_name) {
+                    continue;
+                }
+                visited_fragments.push(spread.fragment_name);
+                if let Some(selection_set) = fragments.get(spread.fragment_name) {
+                    collect_root_fields(selection_set, fragments, visited_fragments, root_fields);
+                }
+            }
+        }
+    }
+}
+
+pub fn parse_operation(method: &Method, query: &Option<String>, body: Option<&[u8]>) -> Option<GraphqlOperation> {
+    //! Parses GraphQL operation, which would be executed by request. Returns `None`
+    //! if request is not a valid GraphQL request.
+    let parameters = get_request_parameters(method, query, body)?;
+    let document = parse_query::<&str>(parameters.get("query")?.as_str()?).ok()?;
+    let requested_name = parameters.get("operationName").and_then(|name| name.as_str());
+
+    let mut fragments = HashMap::new();
+    let mut operations = vec![];
+    for definition in document.definitions.iter() {
+        match definition {
+            Definition::Fragment(fragment) => {
+                fragments.insert(fragment.name, &fragment.selection_set);
+            },
+            Definition::Operation(operation) => operations.push(match operation {
+                OperationDefinition::SelectionSet(selection_set) => (None, "query", selection_set),
+                OperationDefinition::Query(query) => (query.name, "query", &query.selection_set),
+                OperationDefinition::Mutation(mutation) => (mutation.name, "mutation", &mutation.selection_set),
+                OperationDefinition::Subscription(subscription) => (subscription.name, "subscription", &subscription.selection_set),
+            }),
+        }
+    }
+
+    // Same rules as servers use to pick operation - by name, or the only one in document.
+    let (operation_name, operation_type, selection_set) = match requested_name {
+        Some(requested_name) => operations.into_iter().find(|(name, _, _)| *name == Some(requested_name))?,
+        None if operations.len() == 1 => operations.pop()?,
+        None => return None,
+    };
+
+    let mut root_fields = vec![];
+    collect_root_fields(selection_set, &fragments, &mut vec![], &mut root_fields);
+
+    Some(
+        GraphqlOperation {
+            operation_name: operation_name.map(|name| name.to_owned()),
+            operation_type: operation_type.to_owned(),
+            root_fields,
+            variables: match parameters.get("variables") {
+                Some(variables @ JsonValue::Object(_)) => variables.to_owned(),
+                _ => json!({}),
+            },
+        }
+    )
+}
+
+fn json_contains(value: &JsonValue, expected: &JsonValue) -> bool {
+    //! Checks if `value` contains `expected`. Objects match if they contain every
+    //! expected key with matching value, anything else must be equal.
+    match (value, expected) {
+        (JsonValue::Object(value), JsonValue::Object(expected)) => expected.iter().all(
+            |(key, expected)| value.get(key).is_some_and(|value| json_contains(value, expected))
+        ),
+        (value, expected) => value == expected,
+    }
+}
+
+fn rule_matches(graphql_rule: &GraphqlRule, operation: &GraphqlOperation) -> bool {
+    //! Checks if operation matches every condition rule specifies.
+    if graphql_rule.operation_name.is_some() && graphql_rule.operation_name != operation.operation_name {
+        return false;
+    }
+    if graphql_rule.operation_type.as_ref().is_some_and(|operation_type| *operation_type != operation.operation_type) {
+        return false;
+    }
+    let root_fields : Vec<String> = serde_json::from_str(&graphql_rule.root_fields).unwrap_or_default();
+    if !root_fields.iter().all(|root_field| operation.root_fields.contains(root_field)) {
+        return false;
+    }
+    match graphql_rule.variables.as_ref().map(|variables| serde_json::from_str(variables)) {
+        Some(Ok(variables)) => json_contains(&operation.variables, &variables),
+        Some(Err(_)) => false,
+        None => true,
+    }
+}
+
+pub fn find_matching_rule(graphql_rules: Vec<GraphqlRule>, operation: &GraphqlOperation) -> Option<GraphqlRule> {
+    //! Returns first rule (in order of creation) matching operation.
+    graphql_rules.into_iter().find(|graphql_rule| rule_matches(graphql_rule, operation))
+}
+
+pub fn mock_response(graphql_rule: &GraphqlRule) -> (Response<Body>, String) {
+    //! Creates response with configured `data` and `errors`. Returns it together with body for journal.
+    let mut response_body = JsonMap::new();
+    if let Some(data) = graphql_rule.response_data.as_ref() {
+        response_body.insert("data".to_owned(), serde_json::from_str(data).unwrap_or(JsonValue::Null));
+    }
+    if let Some(errors) = graphql_rule.response_errors.as_ref() {
+        response_body.insert("errors".to_owned(), serde_json::from_str(errors).unwrap_or(JsonValue::Null));
+    }
+    let response_data = JsonValue::Object(response_body).to_string();
+
+    let mut response = Response::new(Body::from(response_data.clone()));
+    *(response.status_mut()) = StatusCode::from_u16(graphql_rule.response_status_code as u16).unwrap_or(StatusCode::OK);
+    response.headers_mut().insert(header::CONTENT_TYPE, HeaderValue::from_static("application/json; charset=UTF-8"));
+    response.headers_mut().insert(header::SERVER, HeaderValue::from_static("Rockery - Rust Mocking Gateway"));
+    response.headers_mut().insert("X-Mocked", HeaderValue::from_static("1"));
+    (response, response_data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_post(body: &str) -> Option<GraphqlOperation> {
+        parse_operation(&Method::POST, &None, Some(body.as_bytes()))
+    }
+
+    #[test]
+    fn parses_named_operation_of_json_body() {
+        let operation = parse_post(r#"{
+            "query": "query GetUser($id: ID!) { user(id: $id) { name } viewer: me { id } }",
+            "variables": {"id": "1"}
+        }"#).unwrap();
+
+        assert_eq!(operation.operation_name.as_deref(), Some("GetUser"));
+        assert_eq!(operation.operation_type, "query");
+        assert_eq!(operation.root_fields, vec!["user", "me"]);
+        assert_eq!(operation.variables, json!({"id": "1"}));
+    }
+
+    #[test]
+    fn collects_root_fields_of_fragments() {
+        let operation = parse_post(r#"{
+            "query": "mutation { ...Fields ... on Mutation { logout } } fragment Fields on Mutation { login login }"
+        }"#).unwrap();
+
+        assert_eq!(operation.operation_name, None);
+        assert_eq!(operation.operation_type, "mutation");
+        assert_eq!(operation.root_fields, vec!["login", "logout"]);
+        assert_eq!(operation.variables, json!({}));
+    }
+
+    #[test]
+    fn selects_operation_by_name() {
+        let query = "query A { a } subscription B { b }";
+
+        let operation = parse_post(&json!({"query": query, "operationName": "B"}).to_string()).unwrap();
+        assert_eq!(operation.operation_type, "subscription");
+        assert_eq!(operation.root_fields, vec!["b"]);
+
+        assert!(parse_post(&json!({"query": query}).to_string()).is_none());
+        assert!(parse_post(&json!({"query": query, "operationName": "C"}).to_string()).is_none());
+    }
+
+    #[test]
+    fn parses_operation_of_get_request_and_raw_body() {
+        let query = Some("query=%7B%20a%20%7D&variables=%7B%22x%22%3A1%7D".to_owned());
+        let operation = parse_operation(&Method::GET, &query, None).unwrap();
+        assert_eq!(operation.root_fields, vec!["a"]);
+        assert_eq!(operation.variables, json!({"x": 1}));
+
+        assert_eq!(parse_post("{ b }").unwrap().root_fields, vec!["b"]);
+    }
+
+    #[test]
+    fn rejects_invalid_requests() {
+        assert!(parse_post("{").is_none());
+        assert!(parse_post("[]").is_none());
+        assert!(parse_post(r#"{"variables": {}}"#).is_none());
+        assert!(parse_operation(&Method::GET, &None, None).is_none());
+    }
+
+    #[test]
+    fn object_contains_subset_of_keys() {
+        let value = json!({"id": 1, "filter": {"name": "a", "limit": 10}, "tags": ["x", "y"]});
+
+        assert!(json_contains(&value, &json!({})));
+        assert!(json_contains(&value, &json!({"filter": {"name": "a"}})));
+        assert!(json_contains(&value, &json!({"tags": ["x", "y"]})));
+        assert!(!json_contains(&value, &json!({"tags": ["x"]})));
+        assert!(!json_contains(&value, &json!({"filter": {"name": "b"}})));
+        assert!(!json_contains(&value, &json!({"missing": null})));
+        assert!(!json_contains(&json!(1), &json!({})));
+    }
+}
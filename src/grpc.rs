@@ -0,0 +1,348 @@
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use hyper::{Body, HeaderMap, Response};
+use hyper::body::Bytes;
+use hyper::header::{self, HeaderValue};
+use hyper::http::StatusCode;
+use lazy_static::lazy_static;
+use prost::Message;
+use prost_reflect::{DescriptorPool, DynamicMessage, MessageDescriptor, MethodDescriptor};
+use serde_json::{json, Value as JsonValue};
+use tokio::sync::oneshot;
+
+use crate::body::Trailers;
+use crate::db::GrpcRule;
+use crate::errors::FieldError;
+use crate::settings;
+use crate::utils::get_json_optional_string;
+
+/// Status code of successful call.
+const GRPC_STATUS_OK: i64 = 0;
+/// Status code reported, if configured message cannot be encoded.
+const GRPC_STATUS_INTERNAL: i64 = 13;
+/// Highest status code defined by gRPC.
+const GRPC_STATUS_MAX: i64 = 16;
+
+lazy_static! {
+    /// Descriptors of services loaded from `GRPC_DESCRIPTOR_SET`.
+    pub static ref DESCRIPTOR_POOL: Option<DescriptorPool> = settings::GRPC_DESCRIPTOR_SET.as_ref().map(
+        |path| load_descriptor_pool(path).unwrap_or_else(|error| panic!("Invalid gRPC descriptor set: {}", error))
+    );
+}
+
+fn load_descriptor_pool(path: &Path) -> Result<DescriptorPool, String> {
+    //! Loads binary `FileDescriptorSet`, as written by `protoc --include_imports --descriptor_set_out`.
+    let descriptor_set = fs::read(path).map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+    DescriptorPool::decode(descriptor_set.as_slice()).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+pub fn is_grpc_request(headers: &HeaderMap) -> bool {
+    //! Checks if request is a gRPC call. gRPC-Web is not supported.
+    headers.get(header::CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .map(|content_type| content_type.to_lowercase())
+        .is_some_and(|content_type| content_type == "application/grpc" || content_type.starts_with("application/grpc+"))
+}
+
+pub fn parse_grpc_path(path: &str) -> Option<(&str, &str)> {
+    //! Splits path `/package.Service/Method` into service and method names.
+    let mut segments = path.strip_prefix('/')?.split('/');
+    match (segments.next(), segments.next(), segments.next()) {
+        (Some(service), Some(method), None) if !service.is_empty() && !method.is_empty() => Some((service, method)),
+        _ => None,
+    }
+}
+
+fn find_method(service: &str, method: &str) -> Result<MethodDescriptor, FieldError> {
+    //! Looks up method in loaded descriptor set.
+    let descriptor_pool = DESCRIPTOR_POOL.as_ref().ok_or_else(|| FieldError::new(
+        "_rockery_grpc_service", "ROCKERY_GRPC_DESCRIPTOR_SET must be set in order to mock gRPC methods".to_owned()
+    ))?;
+    let service_descriptor = descriptor_pool.get_service_by_name(service).ok_or_else(|| FieldError::new(
+        "_rockery_grpc_service", format!("Service {} is not defined in gRPC descriptor set", service)
+    ))?;
+    let method_descriptor = service_descriptor.methods().find(|method_descriptor| method_descriptor.name() == method);
+    method_descriptor.ok_or_else(|| FieldError::new(
+        "_rockery_grpc_method", format!("Service {} does not define method {}", service, method)
+    ))
+}
+
+fn encode_message(message_descriptor: MessageDescriptor, message: &JsonValue) -> Result<Bytes, String> {
+    //! Encodes JSON message (in protobuf JSON mapping) into length-prefixed gRPC message.
+    let message = DynamicMessage::deserialize(message_descriptor, message.to_owned()).map_err(|e|e.to_string())?;
+    let encoded = message.encode_to_vec();
+
+    let mut framed = Vec::with_capacity(encoded.len() + 5);
+    framed.push(0);
+    framed.extend_from_slice(&(encoded.len() as u32).to_be_bytes());
+    framed.extend_from_slice(&encoded);
+    Ok(Bytes::from(framed))
+}
+
+pub fn grpc_rule_from_json(parsed_json: &JsonValue) -> Result<GrpcRule, Vec<FieldError>>{
+    //! Creates `GrpcRule` from provided `serde_json::Value` or returns error messages if validation fails.
+    //! Messages are validated against output type of method. Rule is not saved to db!
+    let mut error_messages : Vec<FieldError> = vec![];
+
+    let service = get_json_optional_string(parsed_json, "_rockery_grpc_service", &mut error_messages);
+    let method = get_json_optional_string(parsed_json, "_rockery_grpc_method", &mut error_messages);
+    let (service, method) = match (service, method) {
+        (Some(service), Some(method)) => (service, method),
+        _ => {
+            error_messages.push(FieldError::new("_rockery_grpc_service", "_rockery_grpc_service and _rockery_grpc_method are required".to_owned()));
+            return Err(error_messages);
+        }
+    };
+    let method_descriptor = find_method(&service, &method).map_err(|error| vec![error])?;
+
+    let response_messages : Vec<JsonValue> = match (parsed_json.get("_rockery_grpc_response"), parsed_json.get("_rockery_grpc_responses")) {
+        (Some(_), Some(_)) => {
+            error_messages.push(FieldError::new("_rockery_grpc_response", "_rockery_grpc_response cannot be combined with _rockery_grpc_responses".to_owned()));
+            vec![]
+        },
+        (Some(message), None) => vec![json!({"message": message, "delay_ms": 0})],
+        (None, Some(JsonValue::Array(responses))) => responses.iter().filter_map(|response| {
+            let delay_ms = match response.get("delay_ms") {
+                Some(JsonValue::Null) | None => Some(0),
+                Some(delay_ms) => delay_ms.as_u64(),
+            };
+            match (response.get("message"), delay_ms) {
+                (Some(message), Some(delay_ms)) => Some(json!({"message": message, "delay_ms": delay_ms})),
+                _ => {
+                    error_messages.push(FieldError::new(
+                        "_rockery_grpc_responses",
+                        "_rockery_grpc_responses must contain objects with message and optional non-negative delay_ms".to_owned()
+                    ));
+                    None
+                }
+            }
+        }).collect(),
+        (None, Some(_)) => {
+            error_messages.push(FieldError::new("_rockery_grpc_responses", "_rockery_grpc_responses must be an array of responses".to_owned()));
+            vec![]
+        },
+        (None, None) => vec![],
+    };
+
+    let response_field = if parsed_json.get("_rockery_grpc_responses").is_some() { "_rockery_grpc_responses" } else { "_rockery_grpc_response" };
+    for response_message in response_messages.iter() {
+        if let Err(error) = encode_message(method_descriptor.output(), &response_message["message"]) {
+            error_messages.push(FieldError::new(
+                response_field,
+                format!("Message cannot be encoded as {}: {}", method_descriptor.output().full_name(), error)
+            ));
+        }
+    }
+
+    let status_code = match parsed_json.get("_rockery_grpc_status") {
+        Some(JsonValue::Null) | None => GRPC_STATUS_OK,
+        Some(status_code) => match status_code.as_i64() {
+            Some(status_code) if (GRPC_STATUS_OK..=GRPC_STATUS_MAX).contains(&status_code) => status_code,
+            _ => {
+                error_messages.push(FieldError::new("_rockery_grpc_status", format!("_rockery_grpc_status must be a gRPC status code between 0 and {}", GRPC_STATUS_MAX)));
+                GRPC_STATUS_OK
+            }
+        }
+    };
+    let status_message = get_json_optional_string(parsed_json, "_rockery_grpc_message", &mut error_messages);
+
+    if !method_descriptor.is_server_streaming() && response_messages.len() > 1 {
+        error_messages.push(FieldError::new(
+            "_rockery_grpc_responses",
+            format!("Method {} is not server-streaming and responds with single message only", method)
+        ));
+    }
+    if !method_descriptor.is_server_streaming() && status_code == GRPC_STATUS_OK && response_messages.is_empty() {
+        error_messages.push(FieldError::new("_rockery_grpc_response", "_rockery_grpc_response is required, unless call fails with _rockery_grpc_status".to_owned()));
+    }
+
+    if !error_messages.is_empty() {
+        return Err(error_messages);
+    }
+    Ok(
+        GrpcRule {
+            id: None,
+            service,
+            method,
+            response_messages: JsonValue::Array(response_messages).to_string(),
+            status_code,
+            status_message,
+        }
+    )
+}
+
+fn decode_messages(input: MessageDescriptor, body: &[u8]) -> Option<String> {
+    //! Decodes length-prefixed messages of `input` type into JSON. Multiple
+    //! messages are returned as JSON array.
+    let mut messages = vec![];
+    let mut remaining = body;
+    while remaining.len() >= 5 {
+        let length = u32::from_be_bytes([remaining[1], remaining[2], remaining[3], remaining[4]]) as usize;
+        let encoded = remaining.get(5..5 + length)?;
+        // Compressed messages cannot be decoded.
+        let message = match remaining[0] {
+            0 => DynamicMessage::decode(input.clone(), encoded).ok()
+                .and_then(|message| serde_json::to_value(&message).ok())
+                .unwrap_or(JsonValue::Null),
+            _ => JsonValue::Null,
+        };
+        messages.push(message);
+        remaining = &remaining[5 + length..];
+    }
+    match messages.len() {
+        0 => None,
+        1 => Some(messages[0].to_string()),
+        _ => Some(JsonValue::Array(messages).to_string()),
+    }
+}
+
+pub fn decode_request_data(grpc_rule: &GrpcRule, body: &[u8]) -> Option<String> {
+    //! Decodes request messages into JSON for journal. Multiple messages of
+    //! client-streaming calls are stored as JSON array.
+    decode_messages(find_method(&grpc_rule.service, &grpc_rule.method).ok()?.input(), body)
+}
+
+fn percent_encode_status_message(status_message: &str) -> String {
+    //! Encodes `grpc-message` trailer the way gRPC requires - bytes outside printable ASCII and `%` are escaped.
+    status_message.bytes().map(|byte| match byte {
+        b' '..=b'~' if byte != b'%' => (byte as char).to_string(),
+        _ => format!("%{:02X}", byte),
+    }).collect()
+}
+
+fn status_trailers(status_code: i64, status_message: &Option<String>) -> HeaderMap {
+    //! Creates trailers carrying status of call.
+    let mut trailers = HeaderMap::new();
+    trailers.insert("grpc-status", HeaderValue::from(status_code));
+    if let Some(Ok(status_message)) = status_message.as_ref().map(
+        |status_message| HeaderValue::from_str(&percent_encode_status_message(status_message))
+    ) {
+        trailers.insert("grpc-message", status_message);
+    }
+    trailers
+}
+
+pub fn mock_response(grpc_rule: &GrpcRule) -> (Response<Body>, String) {
+    //! Creates response streaming messages of rule after their delays, followed by status trailers.
+    //! Returns it together with messages serialized for journal.
+    let response_messages : Vec<JsonValue> = serde_json::from_str(&grpc_rule.response_messages).unwrap_or_default();
+    let output = find_method(&grpc_rule.service, &grpc_rule.method)
+        .map(|method_descriptor| method_descriptor.output())
+        .map_err(|error| error.message);
+    let status_code = grpc_rule.status_code;
+    let status_message = grpc_rule.status_message.clone();
+
+    let (mut sender, body) = Body::channel();
+    let (trailers_sender, trailers_receiver) = oneshot::channel();
+    let response_data = JsonValue::Array(
+        response_messages.iter().map(|response_message| response_message["message"].to_owned()).collect()
+    ).to_string();
+
+    tokio::spawn(async move {
+        for response_message in response_messages.iter() {
+            let delay_ms = response_message["delay_ms"].as_u64().unwrap_or(0);
+            if delay_ms > 0 {
+                tokio::time::delay_for(Duration::from_millis(delay_ms)).await;
+            }
+            let encoded = output.clone().and_then(|output| encode_message(output, &response_message["message"]));
+            let encoded = match encoded {
+                Ok(encoded) => encoded,
+                Err(error) => {
+                    let _ = trailers_sender.send(status_trailers(GRPC_STATUS_INTERNAL, &Some(error)));
+                    return;
+                }
+            };
+            if sender.send_data(encoded).await.is_err() {
+                println!("[+] Client disconnected before mocked gRPC stream was finished");
+                return;
+            }
+        }
+        let _ = trailers_sender.send(status_trailers(status_code, &status_message));
+    });
+
+    let mut response = Response::new(body);
+    *(response.status_mut()) = StatusCode::OK;
+    response.headers_mut().insert(header::CONTENT_TYPE, HeaderValue::from_static("application/grpc"));
+    response.headers_mut().insert("X-Mocked", HeaderValue::from_static("1"));
+    response.extensions_mut().insert(Trailers(trailers_receiver));
+    (response, response_data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost_reflect::prost_types::{
+        field_descriptor_proto::{Label, Type}, DescriptorProto, FieldDescriptorProto, FileDescriptorProto,
+    };
+
+    fn hello_request() -> MessageDescriptor {
+        //! Descriptor of `message HelloRequest { string name = 1; }`.
+        let mut descriptor_pool = DescriptorPool::new();
+        descriptor_pool.add_file_descriptor_proto(FileDescriptorProto {
+            name: Some("hello.proto".to_owned()),
+            package: Some("hello".to_owned()),
+            message_type: vec![DescriptorProto {
+                name: Some("HelloRequest".to_owned()),
+                field: vec![FieldDescriptorProto {
+                    name: Some("name".to_owned()),
+                    json_name: Some("name".to_owned()),
+                    number: Some(1),
+                    label: Some(Label::Optional as i32),
+                    r#type: Some(Type::String as i32),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            syntax: Some("proto3".to_owned()),
+            ..Default::default()
+        }).unwrap();
+        descriptor_pool.get_message_by_name("hello.HelloRequest").unwrap()
+    }
+
+    #[test]
+    fn frames_encoded_message_with_length_prefix() {
+        let framed = encode_message(hello_request(), &json!({"name": "rock"})).unwrap();
+
+        assert_eq!(&framed[..], &[0, 0, 0, 0, 6, 0x0a, 4, b'r', b'o', b'c', b'k'][..]);
+        assert!(encode_message(hello_request(), &json!({"unknown": 1})).is_err());
+    }
+
+    #[test]
+    fn decodes_single_and_streamed_messages() {
+        let first = encode_message(hello_request(), &json!({"name": "a"})).unwrap();
+        let second = encode_message(hello_request(), &json!({"name": "b"})).unwrap();
+
+        assert_eq!(decode_messages(hello_request(), &first).unwrap(), r#"{"name":"a"}"#);
+        assert_eq!(
+            decode_messages(hello_request(), &[&first[..], &second[..]].concat()).unwrap(),
+            r#"[{"name":"a"},{"name":"b"}]"#
+        );
+    }
+
+    #[test]
+    fn decodes_compressed_or_invalid_messages_as_null() {
+        let mut compressed = encode_message(hello_request(), &json!({"name": "a"})).unwrap().to_vec();
+        compressed[0] = 1;
+
+        assert_eq!(decode_messages(hello_request(), &compressed).unwrap(), "null");
+        assert_eq!(decode_messages(hello_request(), &[0, 0, 0, 0, 1, 0xff]).unwrap(), "null");
+    }
+
+    #[test]
+    fn rejects_truncated_or_empty_body() {
+        let framed = encode_message(hello_request(), &json!({"name": "rock"})).unwrap();
+
+        assert!(decode_messages(hello_request(), &framed[..framed.len() - 1]).is_none());
+        assert!(decode_messages(hello_request(), &[]).is_none());
+    }
+
+    #[test]
+    fn splits_path_into_service_and_method() {
+        assert_eq!(parse_grpc_path("/hello.Greeter/SayHello"), Some(("hello.Greeter", "SayHello")));
+        assert_eq!(parse_grpc_path("/hello.Greeter"), None);
+        assert_eq!(parse_grpc_path("/hello.Greeter/SayHello/extra"), None);
+        assert_eq!(parse_grpc_path("//SayHello"), None);
+    }
+}
@@ -0,0 +1,180 @@
+use core::str::FromStr;
+
+use chrono::{DateTime, SecondsFormat};
+use hyper::Method;
+use hyper::http::{StatusCode, uri::Uri};
+use serde_json::{json, Value as JsonValue};
+
+use crate::settings;
+use crate::db::{JournalEntry, MockingRule, WebSocketFrame};
+
+/// Version of HAR format produced by `journal_to_har`.
+const HAR_VERSION: &str = "1.2";
+
+fn find_header<'a>(headers: &'a JsonValue, name: &str) -> Option<&'a str> {
+    //! Finds value of header in JSON array produced by `headers_to_json`.
+    headers.as_array()?.iter().find(
+        |header| header["name"].as_str().is_some_and(|n| n.eq_ignore_ascii_case(name))
+    )?["value"].as_str()
+}
+
+fn query_string_to_har(query: &Option<String>) -> JsonValue {
+    //! Decodes query string into HAR `queryString` list.
+    JsonValue::Array(
+        query.as_ref().map(
+            |query| form_urlencoded::parse(query.as_bytes()).map(
+                |(name, value)| json!({"name": name, "value": value})
+            ).collect()
+        ).unwrap_or_default()
+    )
+}
+
+fn journal_entry_to_har(entry: &JournalEntry) -> JsonValue {
+    //! Converts single `JournalEntry` into HAR entry.
+    let request_headers : JsonValue = serde_json::from_str(&entry.request_headers).unwrap_or(json!([]));
+    let response_headers : JsonValue = serde_json::from_str(&entry.response_headers).unwrap_or(json!([]));
+
+    let host = find_header(&request_headers, "Host").map_or(
+        settings::ROCKERY_SOCKET_ADDRESS.to_string(), |host| host.to_owned()
+    );
+    let started_date_time = DateTime::from_timestamp_millis(entry.started_at).map_or(
+        String::new(), |date_time| date_time.to_rfc3339_opts(SecondsFormat::Millis, true)
+    );
+    let status_text = StatusCode::from_u16(entry.response_status_code as u16).ok().and_then(
+        |status_code| status_code.canonical_reason()
+    ).unwrap_or("");
+
+    let mut request = json!({
+        "method": entry.request_method.as_str(),
+        "url": format!("http://{}{}", host, entry.request_url),
+        "httpVersion": "HTTP/1.1",
+        "cookies": [],
+        "headers": request_headers,
+        "queryString": query_string_to_har(&entry.request_query),
+        "headersSize": -1,
+        "bodySize": entry.request_data.as_ref().map_or(0, |data| data.len()),
+    });
+    if let Some(request_data) = &entry.request_data {
+        request["postData"] = json!({
+            "mimeType": find_header(&request_headers, "Content-Type").unwrap_or(""),
+            "text": request_data,
+        });
+    }
+
+    let response_data = entry.response_data.clone().unwrap_or_default();
+    let mut har_entry = json!({
+        "startedDateTime": started_date_time,
+        "time": entry.duration_ms,
+        "request": request,
+        "response": {
+            "status": entry.response_status_code,
+            "statusText": status_text,
+            "httpVersion": "HTTP/1.1",
+            "cookies": [],
+            "headers": response_headers,
+            "content": {
+                "size": response_data.len(),
+                "mimeType": find_header(&response_headers, "Content-Type").unwrap_or(""),
+                "text": response_data,
+            },
+            "redirectURL": find_header(&response_headers, "Location").unwrap_or(""),
+            "headersSize": -1,
+            "bodySize": response_data.len(),
+        },
+        "cache": {},
+        "timings": {
+            "send": 0,
+            "wait": entry.duration_ms,
+            "receive": 0,
+        },
+        "_rockery_mocked": entry.matched_rule_id.is_some(),
+        "_rockery_matched_rule_id": entry.matched_rule_id,
+    });
+
+    // WebSocket frames are exported the same way as browsers do.
+    let frames = entry.id.map_or(Ok(vec![]), WebSocketFrame::find_by_journal_entry_id).unwrap_or_default();
+    if !frames.is_empty() {
+        har_entry["_webSocketMessages"] = JsonValue::Array(
+            frames.iter().map(
+                |frame| json!({
+                    "type": frame.direction,
+                    "time": frame.sent_at as f64 / 1000.0,
+                    "opcode": frame.opcode,
+                    "data": frame.data,
+                })
+            ).collect()
+        );
+    }
+    har_entry
+}
+
+pub fn journal_to_har(entries: &[JournalEntry]) -> JsonValue {
+    //! Serializes journal entries into HAR 1.2 document.
+    json!({
+        "log": {
+            "version": HAR_VERSION,
+            "creator": {
+                "name": "Rockery",
+                "version": env!("CARGO_PKG_VERSION"),
+            },
+            "entries": entries.iter().map(journal_entry_to_har).collect::<Vec<JsonValue>>(),
+        }
+    })
+}
+
+fn har_entry_to_mocking_rule(entry: &JsonValue) -> Result<MockingRule, String> {
+    //! Converts single HAR entry into `MockingRule`. Rule is not saved into database.
+    let request = &entry["request"];
+    let response = &entry["response"];
+
+    let request_method = request["method"].as_str().ok_or("request.method is missing")?;
+    let request_method = Method::from_str(&request_method.to_uppercase()).map_err(|e|e.to_string())?;
+    if !settings::is_interceptable(&request_method){
+        return Err(format!("Method {} cannot be mocked", request_method));
+    }
+
+    let request_uri = Uri::from_str(
+        request["url"].as_str().ok_or("request.url is missing")?
+    ).map_err(|e|e.to_string())?;
+    let request_url = request_uri.path_and_query().map_or("/", |path_and_query| path_and_query.as_str());
+
+    let request_data = request["postData"]["text"].as_str().map(|data| data.trim()).filter(
+        |data| !data.is_empty()
+    ).map(|data| data.to_owned());
+
+    let response_status_code = response["status"].as_i64().ok_or("response.status is missing")?;
+    if StatusCode::from_u16(response_status_code as u16).is_err(){
+        return Err(format!("{} is not valid http status code", response_status_code));
+    }
+
+    if response["content"]["encoding"].as_str().is_some_and(|encoding| !encoding.is_empty()){
+        return Err("Encoded response content is not supported".to_owned());
+    }
+
+    Ok(MockingRule {
+        id: None,
+        request_method,
+        request_host: None,
+        request_url: request_url.to_owned(),
+        request_query: request_uri.query().map(|query| query.to_owned()),
+        request_data,
+        request_form: None,
+        request_files: None,
+        request_xpath: None,
+        request_namespaces: None,
+        request_soap_action: None,
+        response_status_code,
+        response_data: response["content"]["text"].as_str().map(|text| text.to_owned()),
+        response_content_type: None,
+        response_chunks: None,
+        response_sse: false,
+        response_cors: None,
+    })
+}
+
+pub fn har_to_mocking_rules(har: &JsonValue) -> Result<Vec<Result<MockingRule, String>>, String> {
+    //! Converts every entry of HAR document into `MockingRule`. Entries which cannot be
+    //! converted are returned as error messages, so they can be reported one by one.
+    let entries = har["log"]["entries"].as_array().ok_or("HAR document must contain log.entries array")?;
+    Ok(entries.iter().map(har_entry_to_mocking_rule).collect())
+}
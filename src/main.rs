@@ -1,33 +1,167 @@
 mod settings;
+mod errors;
 mod utils;
 mod views;
 mod db;
 mod response;
+mod diagnostics;
+mod har;
+mod traffic_log;
+mod replay;
+mod tls;
+mod upstream;
+mod routing;
+mod proxy;
+mod body;
+mod forwarding;
+mod rewriting;
+mod patching;
+mod websocket;
+mod streaming;
+mod grpc;
+mod graphql;
+mod forms;
+mod xml;
+mod cors;
 
 use std::convert::Infallible;
+use std::env;
+use std::process;
+use std::sync::Arc;
 
 use hyper::{Body, Request, Response, Server, Method};
+use hyper::http::uri::Scheme;
+use hyper::server::conn::{AddrStream, Http};
 use hyper::service::{make_service_fn, service_fn};
+use rustls::ServerConfig;
+use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
 
 use db::{initialize_db, MockingRule};
 use utils::set_env_vars;
-use views::{RuleView};
+use views::{GraphqlRuleView, GrpcRuleView, GrpcView, JournalView, PatchView, ProxyView, RewriteView, RouteView, RuleView, WebSocketRuleView, WebSocketView};
+use websocket::is_websocket_upgrade;
+use grpc::is_grpc_request;
+use replay::{replay, usage, ReplayOptions};
+use upstream::UPSTREAM_TLS_CONFIG;
 use response::HTTPResponse;
+use forwarding::{is_addressed_to_gateway, normalize_http2_request, ClientConnection};
+use body::ResponseBody;
 
 
-async fn handle_request(req: Request<Body>) -> Result<Response<Body>, Infallible> {
-    //! Handles every incoming Request and returns a Response.
+async fn handle_request(mut req: Request<Body>, client: ClientConnection) -> Result<Response<ResponseBody>, Infallible> {
+    //! Handles every incoming Request and returns a Response. Connection
+    //! of client is available to views in request extensions. Views can
+    //! end response with `Trailers` put into response extensions.
+    req.extensions_mut().insert(client);
     println!("[+] {} {}", req.method(), req.uri().to_string());
 
+    // Admin API is matched only for requests meant for gateway, never for ones proxied to other hosts.
+    let admin_path = if is_addressed_to_gateway(req.uri()) { req.uri().path() } else { "" };
+
     // Make this more smarter - Allocating string for URLs can be done more efficiently.
-    let processed_response : Result<Response<Body>, HTTPResponse> = match (req.method(), req.uri().path()) {
+    let processed_response : Result<Response<Body>, HTTPResponse> = match (req.method(), admin_path) {
+        (&Method::CONNECT, _) => ProxyView::connect(req).await,
         (&Method::POST, "/rockery-mock/create-rule") => RuleView::create(req).await,
         (&Method::POST, "/rockery-mock/delete-rule") => RuleView::delete(req).await,
+        (&Method::POST, "/rockery-mock/import-har") => RuleView::import_har(req).await,
+        (&Method::POST, "/rockery-mock/create-route") => RouteView::create(req).await,
+        (&Method::POST, "/rockery-mock/delete-route") => RouteView::delete(req).await,
+        (&Method::GET, "/rockery-mock/routes") => RouteView::list(req).await,
+        (&Method::POST, "/rockery-mock/create-rewrite") => RewriteView::create(req).await,
+        (&Method::POST, "/rockery-mock/delete-rewrite") => RewriteView::delete(req).await,
+        (&Method::GET, "/rockery-mock/rewrites") => RewriteView::list(req).await,
+        (&Method::POST, "/rockery-mock/create-patch") => PatchView::create(req).await,
+        (&Method::POST, "/rockery-mock/delete-patch") => PatchView::delete(req).await,
+        (&Method::GET, "/rockery-mock/patches") => PatchView::list(req).await,
+        (&Method::POST, "/rockery-mock/create-ws-rule") => WebSocketRuleView::create(req).await,
+        (&Method::POST, "/rockery-mock/delete-ws-rule") => WebSocketRuleView::delete(req).await,
+        (&Method::GET, "/rockery-mock/ws-rules") => WebSocketRuleView::list(req).await,
+        (&Method::POST, "/rockery-mock/create-grpc-rule") => GrpcRuleView::create(req).await,
+        (&Method::POST, "/rockery-mock/delete-grpc-rule") => GrpcRuleView::delete(req).await,
+        (&Method::GET, "/rockery-mock/grpc-rules") => GrpcRuleView::list(req).await,
+        (&Method::POST, "/rockery-mock/create-graphql-rule") => GraphqlRuleView::create(req).await,
+        (&Method::POST, "/rockery-mock/delete-graphql-rule") => GraphqlRuleView::delete(req).await,
+        (&Method::GET, "/rockery-mock/graphql-rules") => GraphqlRuleView::list(req).await,
+        (&Method::POST, "/rockery-mock/verify") => JournalView::verify(req).await,
+        (&Method::GET, "/rockery-mock/requests/unmatched") => JournalView::unmatched(req).await,
+        (&Method::GET, "/rockery-mock/requests.har") => JournalView::har(req).await,
+        _ if is_websocket_upgrade(req.headers()) => WebSocketView::proxy(req).await,
+        (&Method::POST, _) if is_grpc_request(req.headers()) => GrpcView::default(req).await,
         _ => RuleView::default(req).await // Regular resend to target
     };
     match processed_response {
-        Ok(resp) => Ok(resp),
-        Err(error_response) => Ok(error_response.as_hyper_response())
+        Ok(resp) => Ok(ResponseBody::from_response(resp)),
+        Err(error_response) => Ok(ResponseBody::from_response(error_response.as_hyper_response()))
+    }
+}
+
+
+async fn run_replay(args: &[String]) {
+    //! Runs `rockery replay` subcommand and exits with non-zero code on any mismatch.
+    let options = ReplayOptions::from_args(args).unwrap_or_else(|error| {
+        println!("[-] {}\n{}", error, usage());
+        process::exit(2);
+    });
+
+    match replay(&options).await {
+        Ok(true) => process::exit(0),
+        Ok(false) => process::exit(1),
+        Err(error) => {
+            println!("[-] Replay failed: {}", error);
+            process::exit(2);
+        }
+    }
+}
+
+
+async fn serve_http() {
+    //! Serves plain HTTP on `ROCKERY_SOCKET_ADDRESS`.
+    let server = Server::bind(&settings::ROCKERY_SOCKET_ADDRESS).serve(
+        make_service_fn(|conn: &AddrStream| {
+            let client = ClientConnection{ address: conn.remote_addr(), secure: false };
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req| handle_request(normalize_http2_request(req), client)))
+            }
+        })
+    );
+
+    if let Err(e) = server.await {
+        panic!("Server error: {}", e);
+    }
+}
+
+
+async fn serve_https(tls_config: ServerConfig) {
+    //! Serves HTTPS on `ROCKERY_SOCKET_ADDRESS`. Every connection is handled
+    //! in its own task, so slow TLS handshake does not block the others.
+    let tls_acceptor = TlsAcceptor::from(Arc::new(tls_config));
+    let mut listener = TcpListener::bind(&*settings::ROCKERY_SOCKET_ADDRESS).await.unwrap_or_else(
+        |e| panic!("Server error: {}", e)
+    );
+    println!("[+] Gateway is serving HTTPS");
+
+    loop {
+        let (stream, remote_address) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                println!("[-] Failed to accept connection: {}", e);
+                continue;
+            }
+        };
+
+        let tls_acceptor = tls_acceptor.clone();
+        let client = ClientConnection{ address: remote_address, secure: true };
+        tokio::spawn(async move {
+            match tls_acceptor.accept(stream).await {
+                Ok(tls_stream) => if let Err(e) = Http::new().serve_connection(
+                    tls_stream, service_fn(move |req| handle_request(normalize_http2_request(req), client))
+                ).with_upgrades().await {
+                    println!("[-] Connection error with {}: {}", remote_address, e);
+                },
+                Err(e) => println!("[-] TLS handshake with {} failed: {}", remote_address, e),
+            }
+        });
     }
 }
 
@@ -35,6 +169,12 @@ async fn handle_request(req: Request<Body>) -> Result<Response<Body>, Infallible
 #[tokio::main]
 async fn main() {
     set_env_vars();
+
+    let args : Vec<String> = env::args().collect();
+    if args.get(1).map(|arg| arg.as_str()) == Some("replay") {
+        return run_replay(&args[2..]).await;
+    }
+
     initialize_db().unwrap();
 
     println!(
@@ -44,20 +184,56 @@ async fn main() {
     );
 
     println!(
-        "[+] All requests will be redirected to {}:{}",
+        "[+] All requests will be redirected to {}://{}:{}",
+        *settings::TARGET_SCHEME,
         settings::TARGET_SOCKET_ADDRESS.ip(),
         settings::TARGET_SOCKET_ADDRESS.port()
     );
 
+    if *settings::TARGET_SCHEME == Scheme::HTTPS {
+        // Fail fast on invalid certificates, rather than on first proxied request.
+        lazy_static::initialize(&UPSTREAM_TLS_CONFIG);
+        if *settings::TARGET_INSECURE_SKIP_VERIFY {
+            println!("[!] Target certificate verification is disabled!");
+        }
+    }
+
     println!("[+] {} Rules exist", MockingRule::count_all().unwrap());
 
-    let server = Server::bind(&settings::ROCKERY_SOCKET_ADDRESS).serve(
-        make_service_fn(|_conn| async {
-            return Ok::<_, Infallible>(service_fn(handle_request));
-        })
-    );
+    if let Some(path) = &*settings::ROUTES_FILE {
+        let routes_count = routing::load_routing_rules_file(path).unwrap_or_else(
+            |error| panic!("Invalid routes file: {}", error)
+        );
+        println!("[+] {} Routes loaded from {}", routes_count, path.display());
+    }
 
-    if let Err(e) = server.await {
-        panic!("Server error: {}", e);
+    if let Some(descriptor_pool) = &*grpc::DESCRIPTOR_POOL {
+        println!("[+] {} gRPC services loaded from descriptor set", descriptor_pool.services().len());
+    }
+
+    if let Some(path) = &*settings::TRAFFIC_LOG {
+        println!("[+] Traffic will be logged into {}", path.display());
+    }
+
+    if *settings::FORWARD_PROXY {
+        println!("[+] Gateway acts as forward proxy for absolute URIs and CONNECT tunnels");
+    }
+
+    if *settings::ROCKERY_MITM {
+        if !*settings::FORWARD_PROXY {
+            println!("[!] ROCKERY_MITM has no effect, unless ROCKERY_FORWARD_PROXY is enabled");
+        }
+        // Generate CA right away, so clients can trust it before first tunnel is intercepted.
+        lazy_static::initialize(&tls::GENERATED_CA);
+        println!("[+] CONNECT tunnels will be intercepted");
+    }
+
+    if *settings::DEBUG {
+        println!("[+] Debug mode is enabled. Near-miss diagnostics will be reported");
+    }
+
+    match tls::listener_config().unwrap_or_else(|error| panic!("Invalid TLS configuration: {}", error)) {
+        Some(tls_config) => serve_https(tls_config).await,
+        None => serve_http().await,
     }
 }
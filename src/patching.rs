@@ -0,0 +1,135 @@
+use hyper::{HeaderMap, Method};
+use hyper::body::Bytes;
+use hyper::header::{self, HeaderValue};
+use hyper::http::StatusCode;
+use hyper::http::response::Parts;
+use json_patch::Patch;
+use serde_json::Value as JsonValue;
+
+use crate::db::{PatchRule, Record};
+use crate::errors::{DbError, FieldError};
+use crate::rewriting::{
+    get_json_headers_remove, get_json_headers_set, get_json_method, get_json_path_pattern, matches_criteria,
+    remove_headers, set_headers
+};
+use crate::utils::get_json_optional_string;
+
+pub fn patch_rule_from_json(parsed_json: &JsonValue) -> Result<PatchRule, Vec<FieldError>>{
+    //! Creates `PatchRule` from provided `serde_json::Value` or returns error messages if validation fails.
+    //! Rule is not saved to db!
+    let mut error_messages : Vec<FieldError> = vec![];
+
+    let method = get_json_method(parsed_json, "_rockery_patch_method", &mut error_messages);
+    let host = get_json_optional_string(parsed_json, "_rockery_patch_host", &mut error_messages);
+    let path_pattern = get_json_path_pattern(parsed_json, "_rockery_patch_path_pattern", &mut error_messages);
+    let headers_set = get_json_headers_set(parsed_json, "_rockery_patch_headers_set", &mut error_messages);
+    let headers_remove = get_json_headers_remove(parsed_json, "_rockery_patch_headers_remove", &mut error_messages);
+
+    let json_patch = match parsed_json.get("_rockery_patch_json_patch") {
+        Some(JsonValue::Null) | None => None,
+        Some(json_patch) => match serde_json::from_value::<Patch>(json_patch.to_owned()) {
+            Ok(_) => Some(json_patch.to_string()),
+            Err(error) => {
+                error_messages.push(FieldError::new("_rockery_patch_json_patch", format!("_rockery_patch_json_patch must be a valid JSON Patch: {}", error)));
+                None
+            }
+        }
+    };
+    let merge_patch = match parsed_json.get("_rockery_patch_merge_patch") {
+        Some(JsonValue::Null) | None => None,
+        Some(merge_patch) => Some(merge_patch.to_string()),
+    };
+
+    let status_code = match parsed_json.get("_rockery_patch_status_code") {
+        Some(JsonValue::Null) | None => None,
+        Some(status_code) => match status_code.as_u64().map(|status_code| StatusCode::from_u16(status_code as u16)) {
+            Some(Ok(status_code)) => Some(status_code.as_u16() as i64),
+            _ => {
+                error_messages.push(FieldError::new("_rockery_patch_status_code", "_rockery_patch_status_code must be a valid HTTP status code".to_owned()));
+                None
+            }
+        }
+    };
+
+    if json_patch.is_none() && merge_patch.is_none() && status_code.is_none()
+        && headers_set == "{}" && headers_remove == "[]" && error_messages.is_empty() {
+        error_messages.push(FieldError::new(
+            "_rockery_patch_json_patch",
+            "Patch rule must patch body, override status code or set or remove some headers".to_owned()
+        ));
+    }
+
+    if !error_messages.is_empty() {
+        return Err(error_messages);
+    }
+    Ok(
+        PatchRule {
+            id: None,
+            method,
+            host: host.map(|host| host.to_lowercase()),
+            path_pattern,
+            json_patch,
+            merge_patch,
+            status_code,
+            headers_set,
+            headers_remove,
+        }
+    )
+}
+
+pub fn find_patch_rules(method: &Method, host: &Option<String>, path: &str) -> Result<Vec<PatchRule>, DbError>{
+    //! Returns patch rules applicable to request received by gateway, in order they are applied.
+    Ok(
+        PatchRule::all()?.into_iter().filter(
+            |patch_rule| matches_criteria(
+                &patch_rule.method, &patch_rule.host, &patch_rule.path_pattern, method, host, path
+            )
+        ).collect()
+    )
+}
+
+pub fn patches_body(patch_rules: &[PatchRule]) -> bool{
+    //! Checks whether response body has to be buffered in order to be patched.
+    patch_rules.iter().any(|patch_rule| patch_rule.json_patch.is_some() || patch_rule.merge_patch.is_some())
+}
+
+pub fn patch_response_head(patch_rules: &[PatchRule], parts: &mut Parts){
+    //! Overrides status code and headers of response. Headers are removed before they are set.
+    for patch_rule in patch_rules {
+        if let Some(Ok(status_code)) = patch_rule.status_code.map(|status_code| StatusCode::from_u16(status_code as u16)) {
+            parts.status = status_code;
+        }
+        remove_headers(&mut parts.headers, &patch_rule.headers_remove);
+        set_headers(&mut parts.headers, &patch_rule.headers_set);
+    }
+    parts.headers.insert("X-Patched", HeaderValue::from_static("1"));
+}
+
+pub fn patch_response_body(patch_rules: &[PatchRule], headers: &mut HeaderMap, body: &[u8]) -> Result<Bytes, String>{
+    //! Applies JSON Patch and JSON merge patch of rules to JSON response body.
+    if let Some(content_encoding) = headers.get(header::CONTENT_ENCODING).and_then(|value| value.to_str().ok()) {
+        if !content_encoding.eq_ignore_ascii_case("identity") {
+            return Err(format!("Response body is encoded by {} and cannot be patched", content_encoding));
+        }
+    }
+    let mut document : JsonValue = serde_json::from_slice(body).map_err(
+        |e| format!("Response body is not JSON and cannot be patched: {}", e)
+    )?;
+
+    for patch_rule in patch_rules {
+        if let Some(json_patch) = &patch_rule.json_patch {
+            let json_patch : Patch = serde_json::from_str(json_patch).map_err(|e|e.to_string())?;
+            json_patch::patch(&mut document, &json_patch).map_err(
+                |e| format!("JSON Patch of rule #{} failed: {}", patch_rule.id.unwrap_or(0), e)
+            )?;
+        }
+        if let Some(merge_patch) = &patch_rule.merge_patch {
+            let merge_patch : JsonValue = serde_json::from_str(merge_patch).map_err(|e|e.to_string())?;
+            json_patch::merge(&mut document, &merge_patch);
+        }
+    }
+
+    // Length of patched body differs, hyper computes it again.
+    headers.remove(header::CONTENT_LENGTH);
+    Ok(Bytes::from(document.to_string()))
+}
@@ -0,0 +1,63 @@
+use std::convert::Infallible;
+
+use hyper::{Body, Request, Response, upgrade::Upgraded};
+use hyper::http::uri::{Authority, Uri};
+use hyper::server::conn::Http;
+use hyper::service::service_fn;
+use futures::future::{try_join, BoxFuture};
+use tokio::io::{copy, split};
+use tokio::net::TcpStream;
+use tokio_rustls::TlsAcceptor;
+
+use crate::tls::interception_config;
+use crate::forwarding::ClientConnection;
+use crate::body::ResponseBody;
+
+pub async fn tunnel(upgraded: Upgraded, authority: &Authority) -> Result<(), String> {
+    //! Passes CONNECT tunnel through to `authority` untouched, copying bytes in both directions.
+    let target_stream = TcpStream::connect(
+        format!("{}:{}", authority.host(), authority.port_u16().unwrap_or(443))
+    ).await.map_err(|e|e.to_string())?;
+
+    let (mut client_reader, mut client_writer) = split(upgraded);
+    let (mut target_reader, mut target_writer) = split(target_stream);
+    try_join(
+        copy(&mut client_reader, &mut target_writer),
+        copy(&mut target_reader, &mut client_writer)
+    ).await.map_err(|e|e.to_string())?;
+    Ok(())
+}
+
+fn handle_intercepted_request(
+    req: Request<Body>,
+    client: ClientConnection
+) -> BoxFuture<'static, Result<Response<ResponseBody>, Infallible>> {
+    //! Handles decrypted request of intercepted tunnel. Future is boxed, since
+    //! `handle_request` would otherwise be recursive type.
+    Box::pin(crate::handle_request(req, client))
+}
+
+pub async fn intercept(upgraded: Upgraded, authority: Authority, client: ClientConnection) -> Result<(), String> {
+    //! Terminates TLS of CONNECT tunnel using certificate issued by generated CA, and handles
+    //! decrypted requests the same way as any other request, so rules apply to them as well.
+    let tls_stream = TlsAcceptor::from(interception_config(authority.host())?).accept(upgraded).await.map_err(
+        |e| format!("TLS handshake failed: {}", e)
+    )?;
+    let client = ClientConnection{ secure: true, ..client };
+
+    Http::new().serve_connection(
+        tls_stream,
+        service_fn(move |mut req: Request<Body>| {
+            // Requests inside tunnel have origin-form URI. Absolute URI makes them proxied to tunnelled host.
+            let absolute_uri = Uri::builder()
+                .scheme("https")
+                .authority(authority.clone())
+                .path_and_query(req.uri().path_and_query().map_or("/", |path_and_query| path_and_query.as_str()))
+                .build();
+            if let Ok(absolute_uri) = absolute_uri {
+                *(req.uri_mut()) = absolute_uri;
+            }
+            handle_intercepted_request(req, client)
+        })
+    ).with_upgrades().await.map_err(|e|e.to_string())
+}
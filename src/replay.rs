@@ -0,0 +1,269 @@
+use core::str::FromStr;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+use hyper::{Body, Method, Request, body::to_bytes};
+use hyper::header::{HeaderName, HeaderValue};
+use hyper::http::uri::PathAndQuery;
+use serde_json::Value as JsonValue;
+
+use crate::settings;
+use crate::upstream;
+use crate::utils::build_target_uri;
+
+/// Response headers, which differ between two responses by nature and are never compared.
+const VOLATILE_HEADERS: [&str; 9] = [
+    "date", "age", "expires", "last-modified", "etag", "set-cookie", "connection", "keep-alive", "transfer-encoding"
+];
+
+/// Request headers, which are recomputed when recorded request is sent again.
+const RECOMPUTED_HEADERS: [&str; 3] = ["content-length", "transfer-encoding", "connection"];
+
+/// Options of `rockery replay` subcommand.
+#[derive(Debug, Default)]
+pub struct ReplayOptions {
+    pub traffic_log: String,
+    pub ignored_paths: Vec<String>,
+    pub ignored_headers: Vec<String>,
+}
+
+impl ReplayOptions {
+    pub fn from_args(args: &[String]) -> Result<ReplayOptions, String> {
+        //! Parses arguments following `replay` subcommand.
+        let mut options = ReplayOptions::default();
+        let mut args_iter = args.iter();
+
+        while let Some(arg) = args_iter.next() {
+            match arg.as_str() {
+                "--ignore-path" => options.ignored_paths.push(
+                    args_iter.next().ok_or("--ignore-path requires JSON pointer, e.g. /data/timestamp")?.to_owned()
+                ),
+                "--ignore-header" => options.ignored_headers.push(
+                    args_iter.next().ok_or("--ignore-header requires header name")?.to_lowercase()
+                ),
+                flag if flag.starts_with("--") => return Err(format!("Unknown option {}", flag)),
+                traffic_log if options.traffic_log.is_empty() => options.traffic_log = traffic_log.to_owned(),
+                unexpected => return Err(format!("Unexpected argument {}", unexpected)),
+            }
+        }
+
+        if options.traffic_log.is_empty() {
+            return Err("Path to traffic log file is required".to_owned());
+        }
+        Ok(options)
+    }
+}
+
+pub fn usage() -> &'static str {
+    "Usage: rockery replay <file.jsonl> [--ignore-path <json-pointer>]... [--ignore-header <name>]..."
+}
+
+fn headers_from_json(headers: &JsonValue) -> BTreeMap<String, String> {
+    //! Reads headers serialized by `headers_to_json`. Repeated headers are joined by `, `.
+    let mut output : BTreeMap<String, String> = BTreeMap::new();
+    for header in headers.as_array().into_iter().flatten() {
+        if let (Some(name), Some(value)) = (header["name"].as_str(), header["value"].as_str()) {
+            output.entry(name.to_lowercase()).and_modify(
+                |existing| { existing.push_str(", "); existing.push_str(value) }
+            ).or_insert_with(|| value.to_owned());
+        }
+    }
+    output
+}
+
+fn diff_json(expected: &JsonValue, actual: &JsonValue, path: String, diffs: &mut Vec<String>) {
+    //! Recursively compares two JSON values and describes every differing path.
+    match (expected, actual) {
+        (JsonValue::Object(expected_map), JsonValue::Object(actual_map)) => {
+            for (key, expected_value) in expected_map {
+                let key_path = format!("{}/{}", path, key.replace('~', "~0").replace('/', "~1"));
+                match actual_map.get(key) {
+                    Some(actual_value) => diff_json(expected_value, actual_value, key_path, diffs),
+                    None => diffs.push(format!("body {}: expected {}, but it is missing", key_path, expected_value)),
+                }
+            }
+            for (key, actual_value) in actual_map {
+                if !expected_map.contains_key(key) {
+                    let key_path = format!("{}/{}", path, key.replace('~', "~0").replace('/', "~1"));
+                    diffs.push(format!("body {}: unexpected {}", key_path, actual_value));
+                }
+            }
+        },
+        (JsonValue::Array(expected_items), JsonValue::Array(actual_items)) if expected_items.len() == actual_items.len() => {
+            for (index, (expected_item, actual_item)) in expected_items.iter().zip(actual_items).enumerate() {
+                diff_json(expected_item, actual_item, format!("{}/{}", path, index), diffs);
+            }
+        },
+        _ if expected != actual => diffs.push(
+            format!("body {}: expected {}, received {}", if path.is_empty() { "/" } else { &path }, expected, actual)
+        ),
+        _ => ()
+    }
+}
+
+fn remove_json_path(value: &mut JsonValue, pointer: &str) {
+    //! Removes value on JSON pointer, so it is not compared.
+    let (parent_pointer, key) = match pointer.rfind('/') {
+        Some(index) => (&pointer[..index], pointer[index + 1..].replace("~1", "/").replace("~0", "~")),
+        None => return,
+    };
+    match value.pointer_mut(parent_pointer) {
+        Some(JsonValue::Object(map)) => { map.remove(&key); },
+        Some(JsonValue::Array(items)) => if let Ok(index) = key.parse::<usize>() {
+            if index < items.len() {
+                items.remove(index);
+            }
+        },
+        _ => ()
+    }
+}
+
+fn diff_responses(
+    recorded: &JsonValue,
+    status_code: u16,
+    headers: &BTreeMap<String, String>,
+    body: &str,
+    options: &ReplayOptions
+) -> Vec<String> {
+    //! Compares live response with the recorded one, and describes every difference.
+    let mut diffs : Vec<String> = vec![];
+
+    let recorded_status_code = recorded["status_code"].as_i64().unwrap_or(0);
+    if recorded_status_code != status_code as i64 {
+        diffs.push(format!("status: expected {}, received {}", recorded_status_code, status_code));
+    }
+
+    let is_ignored_header = |name: &str| VOLATILE_HEADERS.contains(&name) || options.ignored_headers.iter().any(|h| h == name);
+    let recorded_headers = headers_from_json(&recorded["headers"]);
+    for (name, recorded_value) in recorded_headers.iter().filter(|(name, _)| !is_ignored_header(name)) {
+        match headers.get(name) {
+            Some(value) if value == recorded_value => (),
+            Some(value) => diffs.push(format!("header {}: expected {:?}, received {:?}", name, recorded_value, value)),
+            None => diffs.push(format!("header {}: expected {:?}, but it is missing", name, recorded_value)),
+        }
+    }
+    for (name, value) in headers.iter().filter(|(name, _)| !is_ignored_header(name)) {
+        if !recorded_headers.contains_key(name) {
+            diffs.push(format!("header {}: unexpected {:?}", name, value));
+        }
+    }
+
+    let recorded_body = recorded["data"].as_str().unwrap_or("");
+    match (serde_json::from_str::<JsonValue>(recorded_body), serde_json::from_str::<JsonValue>(body)) {
+        (Ok(mut expected_json), Ok(mut actual_json)) => {
+            for pointer in &options.ignored_paths {
+                remove_json_path(&mut expected_json, pointer);
+                remove_json_path(&mut actual_json, pointer);
+            }
+            diff_json(&expected_json, &actual_json, String::new(), &mut diffs);
+        },
+        _ if recorded_body != body => diffs.push(
+            format!("body: expected {} bytes, received {} bytes of different content", recorded_body.len(), body.len())
+        ),
+        _ => ()
+    }
+    diffs
+}
+
+fn build_request(recorded: &JsonValue) -> Result<Request<Body>, String> {
+    //! Rebuilds recorded request, so it can be sent to target again.
+    let method = Method::from_str(recorded["method"].as_str().ok_or("request method is missing")?).map_err(|e|e.to_string())?;
+    let path_and_query = PathAndQuery::from_str(recorded["url"].as_str().ok_or("request url is missing")?).map_err(|e|e.to_string())?;
+
+    let mut request = Request::new(Body::from(recorded["data"].as_str().unwrap_or("").to_owned()));
+    *(request.method_mut()) = method;
+    *(request.uri_mut()) = build_target_uri(path_and_query);
+
+    for header in recorded["headers"].as_array().into_iter().flatten() {
+        if let (Some(name), Some(value)) = (header["name"].as_str(), header["value"].as_str()) {
+            if RECOMPUTED_HEADERS.contains(&name.to_lowercase().as_str()) {
+                continue;
+            }
+            request.headers_mut().append(
+                HeaderName::from_str(name).map_err(|e|e.to_string())?,
+                HeaderValue::from_str(value).map_err(|e|e.to_string())?
+            );
+        }
+    }
+
+    if *settings::SPOOF_HOST_HEADER{
+        request.headers_mut().insert(
+            "Host", HeaderValue::from_static(&settings::TARGET_HOST)
+        );
+    }
+    Ok(request)
+}
+
+async fn replay_entry(entry: &JsonValue, options: &ReplayOptions) -> Result<Vec<String>, String> {
+    //! Sends single recorded request to target and compares responses.
+    let request = build_request(&entry["request"])?;
+    let (parts, body) = upstream::send(request).await.map_err(|e|e.describe())?.into_parts();
+    let body = to_bytes(body).await.map_err(|e|e.to_string())?;
+
+    let mut headers : BTreeMap<String, String> = BTreeMap::new();
+    for (name, value) in parts.headers.iter() {
+        let value = String::from_utf8_lossy(value.as_bytes());
+        headers.entry(name.as_str().to_owned()).and_modify(
+            |existing| { existing.push_str(", "); existing.push_str(&value) }
+        ).or_insert_with(|| value.into_owned());
+    }
+
+    Ok(diff_responses(&entry["response"], parts.status.as_u16(), &headers, &String::from_utf8_lossy(&body), options))
+}
+
+pub async fn replay(options: &ReplayOptions) -> Result<bool, String> {
+    //! Re-sends requests recorded in traffic log to target and prints diff report.
+    //! Mocked exchanges are skipped, as they never reached the target.
+    //! Returns `true` if every live response matched the recorded one.
+    let file = File::open(&options.traffic_log).map_err(|e| format!("Cannot open {}: {}", options.traffic_log, e))?;
+
+    println!(
+        "[+] Replaying {} against {}://{}",
+        options.traffic_log,
+        *settings::TARGET_SCHEME,
+        *settings::TARGET_SOCKET_ADDRESS
+    );
+
+    let (mut matched, mut mismatched, mut skipped) = (0, 0, 0);
+    for (line_number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e|e.to_string())?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry : JsonValue = serde_json::from_str(&line).map_err(
+            |e| format!("Line {} is not valid JSON: {}", line_number + 1, e)
+        )?;
+        if entry["mocked"].as_bool().unwrap_or(false) {
+            skipped += 1;
+            continue;
+        }
+
+        let label = format!(
+            "line {}: {} {}",
+            line_number + 1,
+            entry["request"]["method"].as_str().unwrap_or("?"),
+            entry["request"]["url"].as_str().unwrap_or("?")
+        );
+        match replay_entry(&entry, options).await {
+            Ok(diffs) if diffs.is_empty() => {
+                matched += 1;
+                println!("[+] {} OK", label);
+            },
+            Ok(diffs) => {
+                mismatched += 1;
+                println!("[-] {} MISMATCH", label);
+                for diff in diffs {
+                    println!("      {}", diff);
+                }
+            },
+            Err(error) => {
+                mismatched += 1;
+                println!("[-] {} FAILED: {}", label, error);
+            },
+        }
+    }
+
+    println!("[+] {} matched, {} mismatched, {} mocked skipped", matched, mismatched, skipped);
+    Ok(mismatched == 0)
+}
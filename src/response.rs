@@ -10,6 +10,7 @@ impl HTTPResponse {
     pub fn as_hyper_response(&self) -> Response<Body>{
         Response::builder()
             .status(self.status_code)
+            .header("Content-Type", "application/json; charset=UTF-8")
             .body(Body::from(self.body.clone()))
             .unwrap()
     }
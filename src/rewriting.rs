@@ -0,0 +1,323 @@
+use core::str::FromStr;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use hyper::{HeaderMap, Method};
+use hyper::header::{HeaderName, HeaderValue};
+use hyper::http::uri::{PathAndQuery, Uri};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde_json::{json, Value as JsonValue};
+
+use crate::db::{Record, RewriteRule};
+use crate::errors::{DbError, FieldError};
+use crate::utils::get_json_optional_string;
+
+lazy_static! {
+    /// Path patterns of rules compiled so far, so they are not compiled again for every request.
+    static ref COMPILED_PATTERNS: Mutex<HashMap<String, Regex>> = Mutex::new(HashMap::new());
+}
+
+pub fn get_json_headers_set(
+    parsed_json: &JsonValue,
+    field_name: &str,
+    error_messages: &mut Vec<FieldError>
+) -> String{
+    //! Extracts and validates object of header names and values. Returns it serialized.
+    match parsed_json.get(field_name){
+        Some(JsonValue::Null) | None => "{}".to_owned(),
+        Some(JsonValue::Object(headers)) => {
+            for (name, value) in headers.iter() {
+                if HeaderName::from_str(name).is_err() {
+                    error_messages.push(FieldError::new(field_name, format!("{} contains invalid header name {}", field_name, name)));
+                }
+                match value.as_str().map(HeaderValue::from_str) {
+                    Some(Ok(_)) => (),
+                    _ => error_messages.push(FieldError::new(field_name, format!("{} must contain valid string value of header {}", field_name, name))),
+                }
+            }
+            JsonValue::Object(headers.to_owned()).to_string()
+        },
+        Some(_) => {
+            error_messages.push(FieldError::new(field_name, format!("{} must be an object of header names and values", field_name)));
+            "{}".to_owned()
+        }
+    }
+}
+
+pub fn get_json_headers_remove(
+    parsed_json: &JsonValue,
+    field_name: &str,
+    error_messages: &mut Vec<FieldError>
+) -> String{
+    //! Extracts and validates array of header names. Returns it serialized.
+    match parsed_json.get(field_name){
+        Some(JsonValue::Null) | None => "[]".to_owned(),
+        Some(JsonValue::Array(names)) => {
+            for name in names.iter() {
+                match name.as_str().map(HeaderName::from_str) {
+                    Some(Ok(_)) => (),
+                    _ => error_messages.push(FieldError::new(field_name, format!("{} contains invalid header name {}", field_name, name))),
+                }
+            }
+            JsonValue::Array(names.to_owned()).to_string()
+        },
+        Some(_) => {
+            error_messages.push(FieldError::new(field_name, format!("{} must be an array of header names", field_name)));
+            "[]".to_owned()
+        }
+    }
+}
+
+pub fn rewrite_rule_from_json(parsed_json: &JsonValue) -> Result<RewriteRule, Vec<FieldError>>{
+    //! Creates `RewriteRule` from provided `serde_json::Value` or returns error messages if validation fails.
+    //! Rule is not saved to db!
+    let mut error_messages : Vec<FieldError> = vec![];
+
+    let method = get_json_method(parsed_json, "_rockery_rewrite_method", &mut error_messages);
+    let host = get_json_optional_string(parsed_json, "_rockery_rewrite_host", &mut error_messages);
+    let path_pattern = get_json_path_pattern(parsed_json, "_rockery_rewrite_path_pattern", &mut error_messages);
+    let path_replacement = get_json_optional_string(parsed_json, "_rockery_rewrite_path_replacement", &mut error_messages);
+    let request_headers_set = get_json_headers_set(parsed_json, "_rockery_rewrite_request_headers_set", &mut error_messages);
+    let request_headers_remove = get_json_headers_remove(parsed_json, "_rockery_rewrite_request_headers_remove", &mut error_messages);
+    let response_headers_set = get_json_headers_set(parsed_json, "_rockery_rewrite_response_headers_set", &mut error_messages);
+    let response_headers_remove = get_json_headers_remove(parsed_json, "_rockery_rewrite_response_headers_remove", &mut error_messages);
+
+    if path_replacement.is_some() && path_pattern.is_none() {
+        error_messages.push(FieldError::new("_rockery_rewrite_path_replacement", "_rockery_rewrite_path_replacement requires _rockery_rewrite_path_pattern".to_owned()));
+    }
+    if path_replacement.is_none() && request_headers_set == "{}" && request_headers_remove == "[]"
+        && response_headers_set == "{}" && response_headers_remove == "[]" {
+        error_messages.push(FieldError::new(
+            "_rockery_rewrite_path_replacement",
+            "Rewrite rule must replace path or set or remove some headers".to_owned()
+        ));
+    }
+
+    if !error_messages.is_empty() {
+        return Err(error_messages);
+    }
+    Ok(
+        RewriteRule {
+            id: None,
+            method,
+            host: host.map(|host| host.to_lowercase()),
+            path_pattern,
+            path_replacement,
+            request_headers_set,
+            request_headers_remove,
+            response_headers_set,
+            response_headers_remove,
+        }
+    )
+}
+
+pub fn get_json_method(
+    parsed_json: &JsonValue,
+    field_name: &str,
+    error_messages: &mut Vec<FieldError>
+) -> Option<Method>{
+    //! Extracts and validates optional HTTP method.
+    match get_json_optional_string(parsed_json, field_name, error_messages) {
+        Some(method) => match Method::from_str(&method.to_uppercase()) {
+            Ok(method) => Some(method),
+            Err(_) => {
+                error_messages.push(FieldError::new(field_name, format!("{} must be a valid HTTP method", field_name)));
+                None
+            }
+        },
+        None => None,
+    }
+}
+
+pub fn get_json_path_pattern(
+    parsed_json: &JsonValue,
+    field_name: &str,
+    error_messages: &mut Vec<FieldError>
+) -> Option<String>{
+    //! Extracts optional path pattern and validates it is a valid regex.
+    let path_pattern = get_json_optional_string(parsed_json, field_name, error_messages);
+    if let Some(Err(error)) = path_pattern.as_ref().map(|path_pattern| Regex::new(path_pattern)) {
+        error_messages.push(FieldError::new(field_name, format!("{} is not a valid regex: {}", field_name, error)));
+    }
+    path_pattern
+}
+
+fn compile_pattern(pattern: &str) -> Result<Regex, regex::Error>{
+    //! Compiles path pattern of rule, or returns the one compiled by earlier request.
+    let mut compiled_patterns = COMPILED_PATTERNS.lock().unwrap();
+    if let Some(regex) = compiled_patterns.get(pattern) {
+        return Ok(regex.clone());
+    }
+    let regex = Regex::new(pattern)?;
+    compiled_patterns.insert(pattern.to_owned(), regex.clone());
+    Ok(regex)
+}
+
+pub fn matches_criteria(
+    rule_method: &Option<Method>,
+    rule_host: &Option<String>,
+    rule_path_pattern: &Option<String>,
+    method: &Method,
+    host: &Option<String>,
+    path: &str
+) -> bool{
+    //! Checks if request matches method, host and path pattern of rule. Criteria not set match anything.
+    if rule_method.as_ref().is_some_and(|rule_method| rule_method != method) {
+        return false;
+    }
+    if rule_host.is_some() && rule_host != host {
+        return false;
+    }
+    match rule_path_pattern {
+        Some(path_pattern) => compile_pattern(path_pattern).is_ok_and(|path_pattern| path_pattern.is_match(path)),
+        None => true
+    }
+}
+
+pub fn find_rewrite_rules(method: &Method, host: &Option<String>) -> Result<Vec<RewriteRule>, DbError>{
+    //! Returns rewrite rules matching method and host of request, in order they are applied.
+    //! Path pattern is matched by `rewrite_request`, since earlier rules can rewrite the path.
+    Ok(
+        RewriteRule::all()?.into_iter().filter(
+            |rewrite_rule| matches_criteria(&rewrite_rule.method, &rewrite_rule.host, &None, method, host, "")
+        ).collect()
+    )
+}
+
+pub fn set_headers(headers: &mut HeaderMap, headers_set: &str){
+    //! Adds headers from serialized JSON object, replacing existing ones.
+    if let Ok(JsonValue::Object(headers_set)) = serde_json::from_str(headers_set) {
+        for (name, value) in headers_set.iter() {
+            let value = value.as_str().unwrap_or("");
+            if let (Ok(name), Ok(value)) = (HeaderName::from_str(name), HeaderValue::from_str(value)) {
+                headers.insert(name, value);
+            }
+        }
+    }
+}
+
+pub fn remove_headers(headers: &mut HeaderMap, headers_remove: &str){
+    //! Removes headers listed in serialized JSON array.
+    let headers_remove : JsonValue = serde_json::from_str(headers_remove).unwrap_or_else(|_| json!([]));
+    for name in headers_remove.as_array().into_iter().flatten() {
+        if let Some(Ok(name)) = name.as_str().map(HeaderName::from_str) {
+            headers.remove(name);
+        }
+    }
+}
+
+pub fn rewrite_request(
+    rewrite_rules: Vec<RewriteRule>,
+    uri: &mut Uri,
+    headers: &mut HeaderMap
+) -> Result<Vec<RewriteRule>, String>{
+    //! Applies rewrite rules to request sent to target and returns the applied ones. Path pattern
+    //! of every rule is matched against path rewritten by rules applied before it. Headers are
+    //! removed before they are set, so a header can be replaced by removing and setting it within single rule.
+    let mut applied_rules = vec![];
+    for rewrite_rule in rewrite_rules {
+        let path_pattern = match &rewrite_rule.path_pattern {
+            Some(path_pattern) => Some(compile_pattern(path_pattern).map_err(|e|e.to_string())?),
+            None => None,
+        };
+        if path_pattern.as_ref().is_some_and(|path_pattern| !path_pattern.is_match(uri.path())) {
+            continue;
+        }
+
+        if let (Some(path_pattern), Some(path_replacement)) = (&path_pattern, &rewrite_rule.path_replacement) {
+            let path = path_pattern.replace(uri.path(), path_replacement.as_str()).into_owned();
+            let path = if path.starts_with('/') { path } else { format!("/{}", path) };
+            let path_and_query = PathAndQuery::from_str(
+                &match uri.query() {
+                    Some(query) => format!("{}?{}", path, query),
+                    None => path,
+                }
+            ).map_err(|e|e.to_string())?;
+
+            let mut parts = uri.clone().into_parts();
+            parts.path_and_query = Some(path_and_query);
+            *uri = Uri::from_parts(parts).map_err(|e|e.to_string())?;
+        }
+        remove_headers(headers, &rewrite_rule.request_headers_remove);
+        set_headers(headers, &rewrite_rule.request_headers_set);
+        applied_rules.push(rewrite_rule);
+    }
+    Ok(applied_rules)
+}
+
+pub fn rewrite_response_headers(rewrite_rules: &[RewriteRule], headers: &mut HeaderMap){
+    //! Applies rewrite rules, which have been applied to request, to headers of response received from target.
+    for rewrite_rule in rewrite_rules {
+        remove_headers(headers, &rewrite_rule.response_headers_remove);
+        set_headers(headers, &rewrite_rule.response_headers_set);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compiles_each_pattern_once() {
+        let first = compile_pattern("^/cached/(\\d+)$").unwrap();
+        let second = compile_pattern("^/cached/(\\d+)$").unwrap();
+
+        assert_eq!(first.as_str(), second.as_str());
+        assert!(COMPILED_PATTERNS.lock().unwrap().contains_key("^/cached/(\\d+)$"));
+        assert!(compile_pattern("(").is_err());
+    }
+
+    fn rewrite_rule(path_pattern: Option<&str>, path_replacement: Option<&str>, request_headers_set: &str) -> RewriteRule {
+        RewriteRule {
+            id: None,
+            method: None,
+            host: None,
+            path_pattern: path_pattern.map(str::to_owned),
+            path_replacement: path_replacement.map(str::to_owned),
+            request_headers_set: request_headers_set.to_owned(),
+            request_headers_remove: "[]".to_owned(),
+            response_headers_set: "{}".to_owned(),
+            response_headers_remove: "[]".to_owned(),
+        }
+    }
+
+    #[test]
+    fn matches_later_rules_against_rewritten_path() {
+        let rewrite_rules = vec![
+            rewrite_rule(Some("^/v1/(.*)$"), Some("/api/$1"), "{}"),
+            rewrite_rule(Some("^/api/"), None, r#"{"X-Api": "1"}"#),
+            rewrite_rule(Some("^/v1/"), None, r#"{"X-Old": "1"}"#),
+        ];
+        let mut uri = Uri::from_static("http://localhost:5000/v1/users?page=2");
+        let mut headers = HeaderMap::new();
+
+        let applied_rules = rewrite_request(rewrite_rules, &mut uri, &mut headers).unwrap();
+
+        assert_eq!(uri, "http://localhost:5000/api/users?page=2");
+        assert_eq!(headers["x-api"], "1");
+        assert!(!headers.contains_key("x-old"));
+        assert_eq!(applied_rules.len(), 2);
+    }
+
+    #[test]
+    fn keeps_leading_slash_of_rewritten_path() {
+        let mut uri = Uri::from_static("/users");
+
+        rewrite_request(vec![rewrite_rule(Some("^/"), Some(""), "{}")], &mut uri, &mut HeaderMap::new()).unwrap();
+
+        assert_eq!(uri, "/users");
+    }
+
+    #[test]
+    fn matches_every_criterion_set() {
+        let host = Some("api.local".to_owned());
+
+        assert!(matches_criteria(&None, &None, &None, &Method::GET, &None, "/any"));
+        assert!(matches_criteria(&Some(Method::GET), &host, &Some("^/users/\\d+$".to_owned()), &Method::GET, &host, "/users/1"));
+        assert!(!matches_criteria(&Some(Method::POST), &None, &None, &Method::GET, &host, "/users/1"));
+        assert!(!matches_criteria(&None, &host, &None, &Method::GET, &None, "/users/1"));
+        assert!(!matches_criteria(&None, &None, &Some("^/users/\\d+$".to_owned()), &Method::GET, &host, "/users/me"));
+        assert!(!matches_criteria(&None, &None, &Some("(".to_owned()), &Method::GET, &host, "/users/1"));
+    }
+}
@@ -0,0 +1,296 @@
+use core::str::FromStr;
+use std::fs;
+use std::path::Path;
+
+use hyper::HeaderMap;
+use hyper::http::uri::{PathAndQuery, Scheme, Uri};
+use serde_json::Value as JsonValue;
+
+use crate::errors::FieldError;
+use crate::settings;
+use crate::db::{Record, RoutingRule};
+use crate::utils::{build_target_uri, get_json_optional_string};
+
+/// Target, where proxied request will be sent.
+#[derive(Debug)]
+pub struct ProxyTarget {
+    pub uri: Uri,
+    /// Hostname of target, used if `SPOOF_HOST_HEADER` is enabled.
+    pub host: String,
+}
+
+fn validate_upstream(upstream: &str) -> Result<(), String>{
+    //! Validates upstream is an absolute URL with `http` or `https` scheme and without path.
+    let field_name = "_rockery_route_upstream";
+    let uri = Uri::from_str(upstream).map_err(|_| format!("{} must be a valid URL", field_name))?;
+
+    match uri.scheme() {
+        Some(scheme) if *scheme == Scheme::HTTP || *scheme == Scheme::HTTPS => (),
+        _ => return Err(format!("{} must start with http:// or https://", field_name)),
+    }
+    if uri.authority().is_none() {
+        return Err(format!("{} must contain host", field_name));
+    }
+    if !matches!(uri.path(), "" | "/") || uri.query().is_some() {
+        return Err(format!("{} must not contain path or query. Use _rockery_route_rewrite_prefix instead", field_name));
+    }
+    Ok(())
+}
+
+pub fn routing_rule_from_json(parsed_json: &JsonValue) -> Result<RoutingRule, Vec<FieldError>>{
+    //! Creates `RoutingRule` from provided `serde_json::Value` or returns error messages if validation fails.
+    //! Rule is not saved to db!
+    let mut error_messages : Vec<FieldError> = vec![];
+
+    let path_prefix = get_json_optional_string(parsed_json, "_rockery_route_path_prefix", &mut error_messages);
+    let host = get_json_optional_string(parsed_json, "_rockery_route_host", &mut error_messages);
+    let header_name = get_json_optional_string(parsed_json, "_rockery_route_header_name", &mut error_messages);
+    let header_value = get_json_optional_string(parsed_json, "_rockery_route_header_value", &mut error_messages);
+    let rewrite_prefix = get_json_optional_string(parsed_json, "_rockery_route_rewrite_prefix", &mut error_messages);
+    let upstream = get_json_optional_string(parsed_json, "_rockery_route_upstream", &mut error_messages);
+
+    let strip_prefix = match parsed_json.get("_rockery_route_strip_prefix") {
+        Some(JsonValue::Bool(strip_prefix)) => *strip_prefix,
+        Some(JsonValue::Null) | None => false,
+        Some(_) => {
+            error_messages.push(FieldError::new("_rockery_route_strip_prefix", "_rockery_route_strip_prefix must be a boolean".to_owned()));
+            false
+        }
+    };
+
+    if path_prefix.as_ref().is_some_and(|path_prefix| !path_prefix.starts_with('/')) {
+        error_messages.push(FieldError::new("_rockery_route_path_prefix", "_rockery_route_path_prefix must start with /".to_owned()));
+    }
+    if header_value.is_some() && header_name.is_none() {
+        error_messages.push(FieldError::new("_rockery_route_header_value", "_rockery_route_header_value requires _rockery_route_header_name".to_owned()));
+    }
+    if path_prefix.is_none() && (strip_prefix || rewrite_prefix.is_some()) {
+        error_messages.push(FieldError::new(
+            "_rockery_route_strip_prefix",
+            "_rockery_route_strip_prefix and _rockery_route_rewrite_prefix require _rockery_route_path_prefix".to_owned()
+        ));
+    }
+    match &upstream {
+        Some(upstream) => if let Err(error) = validate_upstream(upstream) {
+            error_messages.push(FieldError::new("_rockery_route_upstream", error));
+        },
+        None => error_messages.push(FieldError::new("_rockery_route_upstream", "Field _rockery_route_upstream is required".to_owned())),
+    }
+
+    match upstream {
+        Some(upstream) if error_messages.is_empty() => Ok(
+            RoutingRule {
+                id: None,
+                path_prefix,
+                host,
+                header_name,
+                header_value,
+                upstream: upstream.trim_end_matches('/').to_owned(),
+                strip_prefix,
+                rewrite_prefix,
+            }
+        ),
+        _ => Err(error_messages)
+    }
+}
+
+pub fn load_routing_rules_file(path: &Path) -> Result<usize, String>{
+    //! Creates routing rules from JSON file, containing array of rules in the same
+    //! format as accepted by admin API. Returns count of created rules.
+    let content = fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+    let parsed_json : JsonValue = serde_json::from_str(&content).map_err(
+        |e| format!("{} is not valid JSON: {}", path.display(), e)
+    )?;
+    let items = parsed_json.as_array().ok_or_else(|| format!("{} must contain JSON array of routes", path.display()))?;
+
+    for (index, item) in items.iter().enumerate() {
+        let mut routing_rule = routing_rule_from_json(item).map_err(
+            |error_messages| format!("Route #{} in {} is invalid: {}", index, path.display(), error_messages.iter().map(|error| error.to_string()).collect::<Vec<String>>().join(", "))
+        )?;
+        routing_rule.create()?;
+    }
+    Ok(items.len())
+}
+
+fn strip_path_prefix<'a>(path: &'a str, path_prefix: &str) -> Option<&'a str>{
+    //! Returns rest of `path` after `path_prefix`, which is either empty or starts with `/`.
+    //! Prefix matches whole path segments only, so `/api` matches `/api/users`, but not `/apiv2`.
+    let rest = path.strip_prefix(path_prefix.trim_end_matches('/'))?;
+    if rest.is_empty() || rest.starts_with('/') { Some(rest) } else { None }
+}
+
+fn matches_request(routing_rule: &RoutingRule, uri: &Uri, headers: &HeaderMap) -> bool{
+    //! Checks if request matches every criterion of `routing_rule`.
+    if let Some(path_prefix) = &routing_rule.path_prefix {
+        if strip_path_prefix(uri.path(), path_prefix).is_none() {
+            return false;
+        }
+    }
+
+    if let Some(host) = &routing_rule.host {
+        let request_host = headers.get("Host").and_then(|value| value.to_str().ok()).or_else(|| uri.host());
+        // Port is not part of comparison, so `api.local` matches `api.local:3000` as well.
+        match request_host.and_then(|request_host| request_host.split(':').next()) {
+            Some(request_host) if request_host.eq_ignore_ascii_case(host) => (),
+            _ => return false,
+        }
+    }
+
+    if let Some(header_name) = &routing_rule.header_name {
+        match (headers.get(header_name.as_str()), &routing_rule.header_value) {
+            (Some(_), None) => (),
+            (Some(value), Some(expected_value)) if value.as_bytes() == expected_value.as_bytes() => (),
+            _ => return false,
+        }
+    }
+    true
+}
+
+fn rewrite_path_and_query(routing_rule: &RoutingRule, uri: &Uri) -> Result<PathAndQuery, String>{
+    //! Strips or rewrites matched path prefix of request, if rule requires so.
+    let rest = routing_rule.path_prefix.as_ref().and_then(|path_prefix| strip_path_prefix(uri.path(), path_prefix));
+    let path = match (rest, routing_rule.strip_prefix, &routing_rule.rewrite_prefix) {
+        (Some(rest), _, Some(rewrite_prefix)) => format!("{}{}", rewrite_prefix.trim_end_matches('/'), rest),
+        (Some(rest), true, None) => rest.to_owned(),
+        _ => uri.path().to_owned(),
+    };
+    let path = if path.starts_with('/') { path } else { format!("/{}", path) };
+
+    PathAndQuery::from_str(
+        &match uri.query() {
+            Some(query) => format!("{}?{}", path, query),
+            None => path,
+        }
+    ).map_err(|e|e.to_string())
+}
+
+pub fn resolve_target(uri: &Uri, headers: &HeaderMap) -> Result<ProxyTarget, String>{
+    //! Finds target of request using routing rules. First matching rule wins.
+    //! Requests not matching any rule are sent to host from their absolute URI, if
+    //! gateway is forward proxy, or to `TARGET_HOST`/`TARGET_PORT`.
+    for routing_rule in RoutingRule::all()? {
+        if !matches_request(&routing_rule, uri, headers) {
+            continue;
+        }
+
+        let upstream = Uri::from_str(&routing_rule.upstream).map_err(|e|e.to_string())?;
+        let path_and_query = rewrite_path_and_query(&routing_rule, uri)?;
+        let target_uri = Uri::builder()
+            .scheme(upstream.scheme_str().unwrap_or("http"))
+            .authority(upstream.authority().map_or("", |authority| authority.as_str()))
+            .path_and_query(path_and_query)
+            .build()
+            .map_err(|e|e.to_string())?;
+
+        return Ok(ProxyTarget {
+            uri: target_uri,
+            host: upstream.host().unwrap_or("").to_owned(),
+        });
+    }
+
+    // Rockery acts as a forward proxy for requests with absolute URI, if enabled.
+    if let (true, Some(_), Some(host)) = (*settings::FORWARD_PROXY, uri.scheme(), uri.host()) {
+        return Ok(ProxyTarget {
+            uri: uri.clone(),
+            host: host.to_owned(),
+        });
+    }
+
+    Ok(ProxyTarget {
+        uri: build_target_uri(
+            uri.path_and_query().cloned().unwrap_or_else(|| PathAndQuery::from_static("/"))
+        ),
+        host: settings::TARGET_HOST.to_owned(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn routing_rule(path_prefix: Option<&str>) -> RoutingRule {
+        RoutingRule {
+            id: None,
+            path_prefix: path_prefix.map(str::to_owned),
+            host: None,
+            header_name: None,
+            header_value: None,
+            upstream: "http://localhost:8080".to_owned(),
+            strip_prefix: false,
+            rewrite_prefix: None,
+        }
+    }
+
+    fn uri(value: &str) -> Uri {
+        Uri::from_str(value).unwrap()
+    }
+
+    #[test]
+    fn matches_path_prefix_on_whole_segments() {
+        let rule = routing_rule(Some("/api"));
+        let headers = HeaderMap::new();
+
+        assert!(matches_request(&rule, &uri("/api"), &headers));
+        assert!(matches_request(&rule, &uri("/api/users?page=2"), &headers));
+        assert!(!matches_request(&rule, &uri("/apiv2/users"), &headers));
+        assert!(!matches_request(&rule, &uri("/"), &headers));
+        assert!(matches_request(&routing_rule(Some("/api/")), &uri("/api"), &headers));
+    }
+
+    #[test]
+    fn matches_host_regardless_of_port() {
+        let mut rule = routing_rule(None);
+        rule.host = Some("api.local".to_owned());
+        let mut headers = HeaderMap::new();
+
+        assert!(!matches_request(&rule, &uri("/"), &headers));
+        assert!(matches_request(&rule, &uri("http://API.local/"), &headers));
+        headers.insert("Host", "api.local:3000".parse().unwrap());
+        assert!(matches_request(&rule, &uri("/"), &headers));
+        headers.insert("Host", "other.local".parse().unwrap());
+        assert!(!matches_request(&rule, &uri("/"), &headers));
+    }
+
+    #[test]
+    fn matches_header_presence_or_value() {
+        let mut rule = routing_rule(None);
+        rule.header_name = Some("X-Version".to_owned());
+        let mut headers = HeaderMap::new();
+
+        assert!(!matches_request(&rule, &uri("/"), &headers));
+        headers.insert("X-Version", "2".parse().unwrap());
+        assert!(matches_request(&rule, &uri("/"), &headers));
+        rule.header_value = Some("2".to_owned());
+        assert!(matches_request(&rule, &uri("/"), &headers));
+        rule.header_value = Some("3".to_owned());
+        assert!(!matches_request(&rule, &uri("/"), &headers));
+    }
+
+    #[test]
+    fn keeps_path_unless_rule_strips_or_rewrites_prefix() {
+        let rule = routing_rule(Some("/api"));
+
+        assert_eq!(rewrite_path_and_query(&rule, &uri("/api/users?page=2")).unwrap(), "/api/users?page=2");
+    }
+
+    #[test]
+    fn strips_prefix_and_keeps_leading_slash() {
+        let mut rule = routing_rule(Some("/api/"));
+        rule.strip_prefix = true;
+
+        assert_eq!(rewrite_path_and_query(&rule, &uri("/api/users?page=2")).unwrap(), "/users?page=2");
+        assert_eq!(rewrite_path_and_query(&rule, &uri("/api")).unwrap(), "/");
+    }
+
+    #[test]
+    fn rewrites_prefix() {
+        let mut rule = routing_rule(Some("/api"));
+        rule.rewrite_prefix = Some("/v1/".to_owned());
+
+        assert_eq!(rewrite_path_and_query(&rule, &uri("/api/users")).unwrap(), "/v1/users");
+        assert_eq!(rewrite_path_and_query(&rule, &uri("/api")).unwrap(), "/v1");
+
+        rule.rewrite_prefix = Some("v2".to_owned());
+        assert_eq!(rewrite_path_and_query(&rule, &uri("/api/users")).unwrap(), "/v2/users");
+    }
+}
@@ -1,19 +1,76 @@
+use core::str::FromStr;
 use std::env;
 use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Mutex;
+use std::time::Duration;
 
 use lazy_static::lazy_static;
 use hyper::Method;
+use hyper::http::uri::Scheme;
 use rusqlite::Connection;
 
 use crate::utils::resolve_to_socket_address;
 
 
-pub const INTERCEPTABLE_METHODS : [Method; 6] = [
+fn env_flag(name: &str) -> bool {
+    //! Reads boolean env variable. Only `1` and `true` are considered as enabled.
+    match env::var(name) {
+        Ok(s) => matches!(s.to_lowercase().as_ref(), "1" | "true"),
+        Err(_) => false
+    }
+}
+
+fn env_path(name: &str) -> Option<PathBuf> {
+    //! Reads optional path from env variable. Empty value is considered as not set.
+    env::var(name).ok().filter(|path| !path.is_empty()).map(PathBuf::from)
+}
+
+fn env_timeout(name: &str, default_ms: u64) -> Option<Duration> {
+    //! Reads timeout in milliseconds from env variable. `0` disables the timeout.
+    let millis = match env::var(name) {
+        Ok(r) => r.parse().unwrap_or_else(|_|panic!("{} is not a valid number of milliseconds", name)),
+        Err(_) => default_ms
+    };
+    if millis == 0 { None } else { Some(Duration::from_millis(millis)) }
+}
+
+/// How HTTP/2 is used for connections to target.
+#[derive(PartialEq)]
+pub enum UpstreamHttp2 {
+    /// Only HTTP/1.1 is spoken.
+    Disabled,
+    /// HTTP/2 is used, if HTTPS target offers it via ALPN.
+    Negotiated,
+    /// HTTP/2 is used for all connections, including plain `http` (h2c).
+    PriorKnowledge,
+}
+
+/// Methods, which can be mocked, unless `ROCKERY_INTERCEPTABLE_METHODS` is set.
+const DEFAULT_INTERCEPTABLE_METHODS : [Method; 6] = [
     Method::GET, Method::HEAD, Method::POST, Method::PUT, Method::DELETE, Method::PATCH
 ];
 
+pub fn is_interceptable(method: &Method) -> bool {
+    //! Checks if requests with `method` can be mocked. `CONNECT` is always tunneled.
+    method != Method::CONNECT && INTERCEPTABLE_METHODS.as_ref().is_none_or(|methods| methods.contains(method))
+}
+
 lazy_static! {
+    /**
+    Methods, which can be mocked, separated by commas. Custom methods (e.g. `PROPFIND`)
+    can be listed too, `*` allows any method. `None` stands for any method.
+    **/
+    pub static ref INTERCEPTABLE_METHODS: Option<Vec<Method>> = match env::var("ROCKERY_INTERCEPTABLE_METHODS") {
+        Ok(methods) if methods.trim() == "*" => None,
+        Ok(methods) => Some(
+            methods.split(',').map(|method| method.trim().to_uppercase()).filter(|method| !method.is_empty()).map(
+                |method| Method::from_str(&method).unwrap_or_else(|_|panic!("ROCKERY_INTERCEPTABLE_METHODS contains invalid method {}", method))
+            ).collect()
+        ),
+        Err(_) => Some(DEFAULT_INTERCEPTABLE_METHODS.to_vec())
+    };
+
     pub static ref ROCKERY_HOST: String = env::var("ROCKERY_HOST").unwrap_or_else(
         |_|"localhost".to_string()
     ).to_owned();
@@ -27,11 +84,45 @@ lazy_static! {
         |_| panic!("{} env variable is not set!", "TARGET_HOST")
     ).to_owned();
     
+    /// Scheme used for requests sent to target. Either `http` (default) or `https`.
+    pub static ref TARGET_SCHEME: Scheme = match env::var("TARGET_SCHEME") {
+        Ok(s) => match s.to_lowercase().as_ref() {
+            "http" => Scheme::HTTP,
+            "https" => Scheme::HTTPS,
+            _ => panic!("TARGET_SCHEME must be either http or https"),
+        },
+        Err(_) => Scheme::HTTP
+    };
+
     pub static ref TARGET_PORT: u16 = match env::var("TARGET_PORT") {
         Ok(r) => r.parse().unwrap_or_else(|_|panic!("TARGET_PORT is not a valid port number")),
+        Err(_) if *TARGET_SCHEME == Scheme::HTTPS => 443,
         Err(_) => 80
     };
     
+    /// PEM certificate chain presented by gateway. Gateway serves HTTPS if set, together with `ROCKERY_TLS_KEY`.
+    pub static ref ROCKERY_TLS_CERT: Option<PathBuf> = env_path("ROCKERY_TLS_CERT");
+
+    /// PEM private key of `ROCKERY_TLS_CERT`.
+    pub static ref ROCKERY_TLS_KEY: Option<PathBuf> = env_path("ROCKERY_TLS_KEY");
+
+    /// Gateway serves HTTPS using certificate issued by CA generated at startup.
+    pub static ref ROCKERY_TLS_AUTO: bool = env_flag("ROCKERY_TLS_AUTO");
+
+    /// Gateway acts as forward proxy - requests with absolute URI are sent to their host and
+    /// CONNECT tunnels are opened. If disabled, absolute URIs are treated as requests to target
+    /// and CONNECT is refused, so gateway cannot be used as open proxy.
+    pub static ref FORWARD_PROXY: bool = env_flag("ROCKERY_FORWARD_PROXY");
+
+    /// Decrypts CONNECT tunnels using certificates issued by generated CA, so rules apply to HTTPS
+    /// traffic of forward proxy as well. If disabled, tunnels are passed through untouched.
+    pub static ref ROCKERY_MITM: bool = env_flag("ROCKERY_MITM");
+
+    /// Path, where CA certificate generated for `ROCKERY_TLS_AUTO` or `ROCKERY_MITM` is written. Defaults to `rockery-ca.pem`.
+    pub static ref ROCKERY_TLS_CA_OUT: PathBuf = env_path("ROCKERY_TLS_CA_OUT").unwrap_or_else(
+        || PathBuf::from("rockery-ca.pem")
+    );
+
     /**
     Global static constant, which uses `Ipv4Addr::LOCALHOST` address
     and `ROCKERY_PORT` env variable, to create `SocketAddr`.
@@ -47,18 +138,124 @@ lazy_static! {
     Global static constant, which uses `TARGET_PORT` and
     `TARGET_HOST` env variable, to create `SocketAddr`. 
     
-     - `TARGET_PORT` defaults to `80`.
+     - `TARGET_PORT` defaults to `80`, or `443` for `https` target.
     **/
     pub static ref TARGET_SOCKET_ADDRESS: SocketAddr = resolve_to_socket_address(
         &TARGET_HOST, &TARGET_PORT
     );
 
-    pub static ref SPOOF_HOST_HEADER: bool = match env::var("SPOOF_HOST_HEADER") {
+    /// PEM bundle of additional CA certificates trusted for `https` target.
+    pub static ref TARGET_CA_BUNDLE: Option<PathBuf> = env_path("TARGET_CA_BUNDLE");
+
+    /// PEM client certificate presented to `https` target. Requires `TARGET_CLIENT_KEY`.
+    pub static ref TARGET_CLIENT_CERT: Option<PathBuf> = env_path("TARGET_CLIENT_CERT");
+
+    /// PEM private key of `TARGET_CLIENT_CERT`.
+    pub static ref TARGET_CLIENT_KEY: Option<PathBuf> = env_path("TARGET_CLIENT_KEY");
+
+    /// Disables verification of target certificate. Use for self-signed development servers only!
+    pub static ref TARGET_INSECURE_SKIP_VERIFY: bool = env_flag("TARGET_INSECURE_SKIP_VERIFY");
+
+    /// Maximum time of establishing TCP connection to target.
+    pub static ref UPSTREAM_CONNECT_TIMEOUT: Option<Duration> = env_timeout("ROCKERY_UPSTREAM_CONNECT_TIMEOUT_MS", 5_000);
+
+    /// Maximum time between two chunks of response body received from target.
+    pub static ref UPSTREAM_READ_TIMEOUT: Option<Duration> = env_timeout("ROCKERY_UPSTREAM_READ_TIMEOUT_MS", 30_000);
+
+    /// Maximum time from sending request to target until its response headers are received.
+    pub static ref UPSTREAM_TOTAL_TIMEOUT: Option<Duration> = env_timeout("ROCKERY_UPSTREAM_TOTAL_TIMEOUT_MS", 60_000);
+
+    /// HTTP/2 usage for connections to target - `off` (default), `alpn` or `prior-knowledge`.
+    pub static ref UPSTREAM_HTTP2: UpstreamHttp2 = match env::var("ROCKERY_UPSTREAM_HTTP2") {
         Ok(s) => match s.to_lowercase().as_ref() {
-                "1" | "true" => true,
-                _ => false,
+            "" | "off" => UpstreamHttp2::Disabled,
+            "alpn" => UpstreamHttp2::Negotiated,
+            "prior-knowledge" => UpstreamHttp2::PriorKnowledge,
+            _ => panic!("ROCKERY_UPSTREAM_HTTP2 must be one of off, alpn or prior-knowledge"),
         },
-        Err(_) => false
+        Err(_) => UpstreamHttp2::Disabled
+    };
+
+    /// Time after which idle keep-alive connections to target are closed.
+    pub static ref UPSTREAM_POOL_IDLE_TIMEOUT: Option<Duration> = env_timeout("ROCKERY_UPSTREAM_POOL_IDLE_TIMEOUT_MS", 90_000);
+
+    /**
+    Origins allowed to access gateway by CORS, separated by commas. `*` allows any origin.
+    If not set, gateway does not handle CORS and preflights are proxied to target.
+    **/
+    pub static ref CORS_ALLOWED_ORIGINS: Option<Vec<String>> = env::var("ROCKERY_CORS_ALLOWED_ORIGINS").ok()
+        .filter(|origins| !origins.is_empty())
+        .map(|origins| origins.split(',').map(|origin| origin.trim().to_owned()).collect());
+
+    /// Allows CORS requests with credentials. Origin is echoed instead of `*`.
+    pub static ref CORS_ALLOW_CREDENTIALS: bool = env_flag("ROCKERY_CORS_ALLOW_CREDENTIALS");
+
+    /// Methods allowed by preflight responses. Defaults to method requested by preflight.
+    pub static ref CORS_ALLOWED_METHODS: Option<String> = env::var("ROCKERY_CORS_ALLOWED_METHODS").ok();
+
+    /// Headers allowed by preflight responses. Defaults to headers requested by preflight.
+    pub static ref CORS_ALLOWED_HEADERS: Option<String> = env::var("ROCKERY_CORS_ALLOWED_HEADERS").ok();
+
+    /// Response headers exposed to CORS requests.
+    pub static ref CORS_EXPOSE_HEADERS: Option<String> = env::var("ROCKERY_CORS_EXPOSE_HEADERS").ok();
+
+    /// Seconds for which browsers may cache preflight responses.
+    pub static ref CORS_MAX_AGE: Option<u64> = env::var("ROCKERY_CORS_MAX_AGE").ok().map(
+        |max_age| max_age.parse().unwrap_or_else(|_|panic!("ROCKERY_CORS_MAX_AGE is not a valid number of seconds"))
+    );
+
+    /// Protobuf descriptor set (`protoc --descriptor_set_out`) of services mocked by gRPC rules.
+    pub static ref GRPC_DESCRIPTOR_SET: Option<PathBuf> = env_path("ROCKERY_GRPC_DESCRIPTOR_SET");
+
+    /// JSON file with routing rules, which are created at startup.
+    pub static ref ROUTES_FILE: Option<PathBuf> = env_path("ROCKERY_ROUTES");
+
+    pub static ref SPOOF_HOST_HEADER: bool = env_flag("SPOOF_HOST_HEADER");
+
+    /// Appends client address to `X-Forwarded-For` header of proxied requests.
+    pub static ref X_FORWARDED_FOR: bool = env_flag("ROCKERY_X_FORWARDED_FOR");
+
+    /// Sets `X-Forwarded-Proto` header of proxied requests to scheme used by client.
+    pub static ref X_FORWARDED_PROTO: bool = env_flag("ROCKERY_X_FORWARDED_PROTO");
+
+    /// Sets `X-Forwarded-Host` header of proxied requests to original `Host` header.
+    pub static ref X_FORWARDED_HOST: bool = env_flag("ROCKERY_X_FORWARDED_HOST");
+
+    /// Appends client address, scheme and original host to `Forwarded` header (RFC 7239) of proxied requests.
+    pub static ref FORWARDED: bool = env_flag("ROCKERY_FORWARDED");
+
+    /**
+    Maximum size in bytes of a body held in memory. Request bodies are buffered
+    only when some mocking rule matches on body, larger ones are rejected. Streamed
+    bodies larger than this are proxied, but not captured in the journal. Defaults to 10 MiB.
+    **/
+    pub static ref MAX_BUFFERED_BODY: usize = match env::var("ROCKERY_MAX_BUFFERED_BODY") {
+        Ok(r) => r.parse().unwrap_or_else(|_|panic!("ROCKERY_MAX_BUFFERED_BODY is not a valid size in bytes")),
+        Err(_) => 10 * 1024 * 1024
+    };
+
+    /**
+    Enables debug mode. Near-miss diagnostics are computed for requests,
+    which did not match any mocking rule.
+    **/
+    pub static ref DEBUG: bool = env_flag("ROCKERY_DEBUG");
+
+    /// Records frames of proxied WebSocket connections into journal.
+    pub static ref WEBSOCKET_LOG: bool = env_flag("ROCKERY_WEBSOCKET_LOG");
+
+    /// Path of JSON Lines file, where every handled exchange is appended. Disabled if not set.
+    pub static ref TRAFFIC_LOG: Option<PathBuf> = env_path("ROCKERY_TRAFFIC_LOG");
+
+    /// Size in bytes, after which traffic log gets rotated. Defaults to 10 MiB.
+    pub static ref TRAFFIC_LOG_MAX_SIZE: u64 = match env::var("ROCKERY_TRAFFIC_LOG_MAX_SIZE") {
+        Ok(r) => r.parse().unwrap_or_else(|_|panic!("ROCKERY_TRAFFIC_LOG_MAX_SIZE is not a valid size in bytes")),
+        Err(_) => 10 * 1024 * 1024
+    };
+
+    /// Count of rotated traffic log files kept on disk. Defaults to `5`.
+    pub static ref TRAFFIC_LOG_MAX_FILES: u32 = match env::var("ROCKERY_TRAFFIC_LOG_MAX_FILES") {
+        Ok(r) => r.parse().unwrap_or_else(|_|panic!("ROCKERY_TRAFFIC_LOG_MAX_FILES is not a valid number")),
+        Err(_) => 5
     };
 
     pub static ref DB : Mutex<Connection> = Mutex::new(
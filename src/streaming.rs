@@ -0,0 +1,165 @@
+use std::time::Duration;
+
+use hyper::Body;
+use serde_json::{json, Value as JsonValue};
+
+use crate::errors::FieldError;
+
+/// Content type of Server-Sent Events stream.
+pub const EVENT_STREAM_CONTENT_TYPE: &str = "text/event-stream; charset=UTF-8";
+
+pub fn get_json_response_chunks(
+    parsed_json: &JsonValue,
+    field_name: &str,
+    error_messages: &mut Vec<FieldError>
+) -> Option<String>{
+    //! Extracts and validates array of response chunks. Chunk is either data itself, or an object
+    //! with `data` and optional `delay_ms`, `event` and `id`. Returns chunks normalized to objects
+    //! and serialized. Non-string data are serialized too.
+    let chunks = match parsed_json.get(field_name) {
+        Some(JsonValue::Null) | None => return None,
+        Some(JsonValue::Array(chunks)) => chunks,
+        Some(_) => {
+            error_messages.push(FieldError::new(field_name, format!("{} must be an array of chunks", field_name)));
+            return None;
+        }
+    };
+
+    let mut normalized_chunks = vec![];
+    for chunk in chunks.iter() {
+        let (data, delay_ms, event, id) = match chunk {
+            JsonValue::Object(chunk) => match chunk.get("data") {
+                Some(data) => (data, chunk.get("delay_ms"), chunk.get("event"), chunk.get("id")),
+                None => {
+                    error_messages.push(FieldError::new(field_name, format!("{} contains chunk object without data", field_name)));
+                    continue;
+                }
+            },
+            data => (data, None, None, None),
+        };
+
+        let delay_ms = match delay_ms {
+            Some(JsonValue::Null) | None => 0,
+            Some(delay_ms) => match delay_ms.as_u64() {
+                Some(delay_ms) => delay_ms,
+                None => {
+                    error_messages.push(FieldError::new(field_name, format!("{} must contain delay_ms as non-negative integer", field_name)));
+                    0
+                }
+            }
+        };
+        for &(name, value) in [("event", event), ("id", id)].iter() {
+            match value {
+                Some(JsonValue::String(value)) if value.contains(&['\r', '\n'][..]) => error_messages.push(FieldError::new(
+                    field_name,
+                    format!("{} must not contain line breaks in {}", field_name, name)
+                )),
+                Some(JsonValue::String(_)) | Some(JsonValue::Null) | None => (),
+                Some(_) => error_messages.push(FieldError::new(field_name, format!("{} must contain {} as string", field_name, name))),
+            }
+        }
+
+        normalized_chunks.push(json!({
+            "data": match data {
+                JsonValue::String(data) => data.to_owned(),
+                data => data.to_string(),
+            },
+            "delay_ms": delay_ms,
+            "event": event.and_then(|event| event.as_str()),
+            "id": id.and_then(|id| id.as_str()),
+        }));
+    }
+    Some(JsonValue::Array(normalized_chunks).to_string())
+}
+
+fn format_chunk(chunk: &JsonValue, sse: bool) -> String{
+    //! Returns chunk as it is sent to client. SSE events carry their id, event type
+    //! and every line of data in separate field.
+    let data = chunk["data"].as_str().unwrap_or("");
+    if !sse {
+        return data.to_owned();
+    }
+
+    let mut event = String::new();
+    if let Some(id) = chunk["id"].as_str() {
+        event.push_str(&format!("id: {}\n", id));
+    }
+    if let Some(event_type) = chunk["event"].as_str() {
+        event.push_str(&format!("event: {}\n", event_type));
+    }
+    for line in data.lines() {
+        event.push_str(&format!("data: {}\n", line));
+    }
+    if data.is_empty() {
+        event.push_str("data:\n");
+    }
+    event.push('\n');
+    event
+}
+
+fn parse_chunks(chunks: &str) -> Vec<JsonValue>{
+    //! Deserializes chunks stored by `get_json_response_chunks`.
+    match serde_json::from_str(chunks) {
+        Ok(JsonValue::Array(chunks)) => chunks,
+        _ => vec![],
+    }
+}
+
+pub fn chunks_to_data(chunks: &str, sse: bool) -> String{
+    //! Returns whole body client receives, used for journal.
+    parse_chunks(chunks).iter().map(|chunk| format_chunk(chunk, sse)).collect()
+}
+
+pub fn stream_chunks(chunks: &str, sse: bool) -> Body{
+    //! Returns body sending each chunk after its delay. Streaming stops when client disconnects.
+    let chunks = parse_chunks(chunks);
+    let (mut sender, body) = Body::channel();
+
+    tokio::spawn(async move {
+        for chunk in chunks.iter() {
+            let delay_ms = chunk["delay_ms"].as_u64().unwrap_or(0);
+            if delay_ms > 0 {
+                tokio::time::delay_for(Duration::from_millis(delay_ms)).await;
+            }
+            if sender.send_data(format_chunk(chunk, sse).into()).await.is_err() {
+                println!("[+] Client disconnected before mocked stream was finished");
+                return;
+            }
+        }
+    });
+    body
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sends_plain_chunk_data_as_is() {
+        assert_eq!(format_chunk(&json!({"data": "a\nb", "id": "1"}), false), "a\nb");
+        assert_eq!(format_chunk(&json!({}), false), "");
+    }
+
+    #[test]
+    fn formats_sse_event_fields() {
+        assert_eq!(
+            format_chunk(&json!({"data": "hello", "id": "7", "event": "greeting"}), true),
+            "id: 7\nevent: greeting\ndata: hello\n\n"
+        );
+    }
+
+    #[test]
+    fn sends_every_line_of_sse_data_in_own_field() {
+        assert_eq!(format_chunk(&json!({"data": "a\nb"}), true), "data: a\ndata: b\n\n");
+        assert_eq!(format_chunk(&json!({"data": ""}), true), "data:\n\n");
+    }
+
+    #[test]
+    fn joins_chunks_into_journaled_body() {
+        let chunks = json!([{"data": "a"}, {"data": "b", "event": "end"}]).to_string();
+
+        assert_eq!(chunks_to_data(&chunks, false), "ab");
+        assert_eq!(chunks_to_data(&chunks, true), "data: a\n\nevent: end\ndata: b\n\n");
+        assert_eq!(chunks_to_data("not json", true), "");
+    }
+}
@@ -0,0 +1,225 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use chrono::{Duration, Utc};
+use lazy_static::lazy_static;
+use rcgen::{
+    BasicConstraints, CertificateParams, DistinguishedName, DnType, ExtendedKeyUsagePurpose, IsCa,
+    KeyUsagePurpose, SanType
+};
+use rustls::{
+    Certificate, ClientConfig, NoClientAuth, PrivateKey, RootCertStore, ServerCertVerified,
+    ServerCertVerifier, ServerConfig, TLSError
+};
+use rustls::internal::pemfile;
+
+use crate::settings::{self, UpstreamHttp2};
+
+lazy_static! {
+    /// CA generated at startup, which issues certificates for `ROCKERY_TLS_AUTO` and for
+    /// intercepted CONNECT tunnels. Its certificate is written to `ROCKERY_TLS_CA_OUT`.
+    pub static ref GENERATED_CA: CertificateAuthority = {
+        let certificate_authority = CertificateAuthority::generate().unwrap_or_else(
+            |error| panic!("Failed to generate CA: {}", error)
+        );
+        certificate_authority.write_pem(&settings::ROCKERY_TLS_CA_OUT).unwrap_or_else(
+            |error| panic!("Failed to write CA: {}", error)
+        );
+        println!("[+] Generated CA certificate has been written to {}", settings::ROCKERY_TLS_CA_OUT.display());
+        certificate_authority
+    };
+
+    /// TLS configurations of intercepted hosts, so certificate is issued only once per host.
+    static ref INTERCEPTION_CONFIGS: Mutex<HashMap<String, Arc<ServerConfig>>> = Mutex::new(HashMap::new());
+}
+
+/// Certificate verifier, which accepts any server certificate.
+/// Used only if `TARGET_INSECURE_SKIP_VERIFY` is enabled, for self-signed development servers.
+struct InsecureServerCertVerifier {}
+
+impl ServerCertVerifier for InsecureServerCertVerifier {
+    fn verify_server_cert(
+        &self,
+        _roots: &RootCertStore,
+        _presented_certs: &[Certificate],
+        _dns_name: webpki::DNSNameRef,
+        _ocsp_response: &[u8]
+    ) -> Result<ServerCertVerified, TLSError> {
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+pub fn load_certificates(path: &Path) -> Result<Vec<Certificate>, String> {
+    //! Loads all PEM encoded certificates from file.
+    let file = File::open(path).map_err(|e| format!("Cannot open {}: {}", path.display(), e))?;
+    let certificates = pemfile::certs(&mut BufReader::new(file)).map_err(
+        |_| format!("{} does not contain valid PEM certificates", path.display())
+    )?;
+
+    if certificates.is_empty() {
+        return Err(format!("{} does not contain any certificate", path.display()));
+    }
+    Ok(certificates)
+}
+
+pub fn load_private_key(path: &Path) -> Result<PrivateKey, String> {
+    //! Loads PEM encoded private key from file. Both PKCS#8 and RSA keys are supported.
+    let read_keys = |reader: fn(&mut dyn std::io::BufRead) -> Result<Vec<PrivateKey>, ()>| {
+        let file = File::open(path).map_err(|e| format!("Cannot open {}: {}", path.display(), e))?;
+        reader(&mut BufReader::new(file)).map_err(
+            |_| format!("{} does not contain valid PEM private key", path.display())
+        )
+    };
+
+    read_keys(pemfile::pkcs8_private_keys)?.into_iter()
+        .chain(read_keys(pemfile::rsa_private_keys)?)
+        .next()
+        .ok_or_else(|| format!("{} does not contain any private key", path.display()))
+}
+
+pub fn client_config() -> Result<ClientConfig, String> {
+    //! Creates TLS configuration for connections to target. Trusts Mozilla root
+    //! certificates and `TARGET_CA_BUNDLE`, and presents client certificate if configured.
+    //! HTTP/2 is offered via ALPN according to `UPSTREAM_HTTP2`.
+    let mut config = ClientConfig::new();
+    config.root_store.add_server_trust_anchors(&webpki_roots::TLS_SERVER_ROOTS);
+
+    if let Some(ca_bundle) = &*settings::TARGET_CA_BUNDLE {
+        for certificate in load_certificates(ca_bundle)? {
+            config.root_store.add(&certificate).map_err(
+                |e| format!("Invalid CA certificate in {}: {}", ca_bundle.display(), e)
+            )?;
+        }
+    }
+
+    match (&*settings::TARGET_CLIENT_CERT, &*settings::TARGET_CLIENT_KEY) {
+        (Some(cert), Some(key)) => config.set_single_client_cert(
+            load_certificates(cert)?, load_private_key(key)?
+        ).map_err(|e| format!("Invalid client certificate: {}", e))?,
+        (None, None) => (),
+        _ => return Err("TARGET_CLIENT_CERT and TARGET_CLIENT_KEY must be set together".to_owned()),
+    }
+
+    config.alpn_protocols = match *settings::UPSTREAM_HTTP2 {
+        UpstreamHttp2::Disabled => vec![b"http/1.1".to_vec()],
+        UpstreamHttp2::Negotiated => vec![b"h2".to_vec(), b"http/1.1".to_vec()],
+        UpstreamHttp2::PriorKnowledge => vec![b"h2".to_vec()],
+    };
+
+    if *settings::TARGET_INSECURE_SKIP_VERIFY {
+        config.dangerous().set_certificate_verifier(Arc::new(InsecureServerCertVerifier {}));
+    }
+    Ok(config)
+}
+
+/// Certificate authority generated at startup, which issues certificates presented by gateway.
+/// Its certificate can be written to disk, so clients are able to trust it.
+pub struct CertificateAuthority {
+    certificate: rcgen::Certificate,
+}
+
+impl CertificateAuthority {
+    pub fn generate() -> Result<CertificateAuthority, String> {
+        //! Generates new self-signed CA certificate.
+        let mut distinguished_name = DistinguishedName::new();
+        distinguished_name.push(DnType::CommonName, "Rockery - Rust Mocking Gateway CA");
+        distinguished_name.push(DnType::OrganizationName, "Rockery");
+
+        let mut params = CertificateParams::default();
+        params.distinguished_name = distinguished_name;
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        params.key_usages = vec![KeyUsagePurpose::KeyCertSign, KeyUsagePurpose::CrlSign];
+        params.not_before = Utc::now() - Duration::days(1);
+        params.not_after = Utc::now() + Duration::days(365);
+
+        Ok(CertificateAuthority {
+            certificate: rcgen::Certificate::from_params(params).map_err(|e|e.to_string())?
+        })
+    }
+
+    pub fn write_pem(&self, path: &Path) -> Result<(), String> {
+        //! Writes PEM encoded CA certificate to `path`.
+        let pem = self.certificate.serialize_pem().map_err(|e|e.to_string())?;
+        fs::write(path, pem).map_err(|e| format!("Cannot write {}: {}", path.display(), e))
+    }
+
+    pub fn issue(&self, hostnames: &[String]) -> Result<(Vec<Certificate>, PrivateKey), String> {
+        //! Issues server certificate valid for `hostnames`, signed by this CA.
+        //! Returns certificate chain together with its private key.
+        let mut distinguished_name = DistinguishedName::new();
+        distinguished_name.push(DnType::CommonName, hostnames.first().map_or("localhost", |h| h.as_str()));
+
+        let mut params = CertificateParams::default();
+        params.distinguished_name = distinguished_name;
+        params.subject_alt_names = hostnames.iter().map(
+            |hostname| match hostname.parse::<IpAddr>() {
+                Ok(ip_address) => SanType::IpAddress(ip_address),
+                Err(_) => SanType::DnsName(hostname.to_owned()),
+            }
+        ).collect();
+        params.key_usages = vec![KeyUsagePurpose::DigitalSignature, KeyUsagePurpose::KeyEncipherment];
+        params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ServerAuth];
+        params.use_authority_key_identifier_extension = true;
+        params.not_before = Utc::now() - Duration::days(1);
+        params.not_after = Utc::now() + Duration::days(365);
+
+        let certificate = rcgen::Certificate::from_params(params).map_err(|e|e.to_string())?;
+        let certificate_der = certificate.serialize_der_with_signer(&self.certificate).map_err(|e|e.to_string())?;
+        let ca_certificate_der = self.certificate.serialize_der().map_err(|e|e.to_string())?;
+
+        Ok((
+            vec![Certificate(certificate_der), Certificate(ca_certificate_der)],
+            PrivateKey(certificate.serialize_private_key_der())
+        ))
+    }
+}
+
+pub fn server_config(certificates: Vec<Certificate>, private_key: PrivateKey) -> Result<ServerConfig, String> {
+    //! Creates TLS configuration for connections accepted by gateway. HTTP/2 is preferred, if client offers it.
+    let mut config = ServerConfig::new(NoClientAuth::new());
+    config.set_single_cert(certificates, private_key).map_err(|e| format!("Invalid certificate: {}", e))?;
+    config.set_protocols(&[b"h2".to_vec(), b"http/1.1".to_vec()]);
+    Ok(config)
+}
+
+pub fn listener_config() -> Result<Option<ServerConfig>, String> {
+    //! Creates TLS configuration for gateway listener from `ROCKERY_TLS_CERT` and `ROCKERY_TLS_KEY`.
+    //! If `ROCKERY_TLS_AUTO` is enabled instead, certificate is issued by newly generated CA, which
+    //! is written to `ROCKERY_TLS_CA_OUT`. Returns `None` if gateway should listen on plain HTTP.
+    match (&*settings::ROCKERY_TLS_CERT, &*settings::ROCKERY_TLS_KEY, *settings::ROCKERY_TLS_AUTO) {
+        (Some(cert), Some(key), false) => Ok(Some(
+            server_config(load_certificates(cert)?, load_private_key(key)?)?
+        )),
+        (None, None, true) => {
+            let mut hostnames = vec!["localhost".to_owned(), "127.0.0.1".to_owned()];
+            for hostname in [settings::ROCKERY_HOST.to_owned(), settings::ROCKERY_SOCKET_ADDRESS.ip().to_string()] {
+                if !hostnames.contains(&hostname) {
+                    hostnames.push(hostname);
+                }
+            }
+            let (certificates, private_key) = GENERATED_CA.issue(&hostnames)?;
+            Ok(Some(server_config(certificates, private_key)?))
+        },
+        (None, None, false) => Ok(None),
+        (_, _, true) => Err("ROCKERY_TLS_AUTO cannot be combined with ROCKERY_TLS_CERT and ROCKERY_TLS_KEY".to_owned()),
+        _ => Err("ROCKERY_TLS_CERT and ROCKERY_TLS_KEY must be set together".to_owned()),
+    }
+}
+
+pub fn interception_config(hostname: &str) -> Result<Arc<ServerConfig>, String> {
+    //! Returns TLS configuration presenting certificate for `hostname`, issued by `GENERATED_CA`.
+    //! Used to decrypt traffic of intercepted CONNECT tunnels.
+    let mut interception_configs = INTERCEPTION_CONFIGS.lock().unwrap();
+    if let Some(config) = interception_configs.get(hostname) {
+        return Ok(config.clone());
+    }
+
+    let (certificates, private_key) = GENERATED_CA.issue(&[hostname.to_owned()])?;
+    let config = Arc::new(server_config(certificates, private_key)?);
+    interception_configs.insert(hostname.to_owned(), config.clone());
+    Ok(config)
+}
@@ -0,0 +1,77 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+
+use lazy_static::lazy_static;
+
+use crate::settings;
+use crate::db::JournalEntry;
+
+lazy_static! {
+    /// Sends lines to single writer thread, so lines of concurrent exchanges never
+    /// interleave and file I/O never blocks handling of requests.
+    static ref TRAFFIC_LOG_SENDER: Option<Sender<String>> = settings::TRAFFIC_LOG.as_ref().map(
+        |path| spawn_writer(path.to_owned())
+    );
+}
+
+fn rotated_path(path: &Path, index: u32) -> PathBuf {
+    //! Returns path of rotated traffic log file, e.g. `traffic.jsonl.1`.
+    let mut rotated = path.as_os_str().to_owned();
+    rotated.push(format!(".{}", index));
+    PathBuf::from(rotated)
+}
+
+fn rotate(path: &Path) -> Result<(), String> {
+    //! Shifts rotated files by one, dropping the oldest one,
+    //! and moves current traffic log to `<path>.1`.
+    let max_files = *settings::TRAFFIC_LOG_MAX_FILES;
+    if max_files == 0 {
+        return fs::remove_file(path).map_err(|e|e.to_string());
+    }
+
+    let oldest = rotated_path(path, max_files);
+    if oldest.exists() {
+        fs::remove_file(&oldest).map_err(|e|e.to_string())?;
+    }
+    for index in (1..max_files).rev() {
+        let rotated = rotated_path(path, index);
+        if rotated.exists() {
+            fs::rename(&rotated, rotated_path(path, index + 1)).map_err(|e|e.to_string())?;
+        }
+    }
+    fs::rename(path, rotated_path(path, 1)).map_err(|e|e.to_string())
+}
+
+fn append_line(path: &Path, line: &str) -> Result<(), String> {
+    //! Appends single line to traffic log. Rotates the file first, if line would exceed its maximum size.
+    let current_size = fs::metadata(path).map(|metadata| metadata.len()).unwrap_or(0);
+    if current_size > 0 && current_size + line.len() as u64 + 1 > *settings::TRAFFIC_LOG_MAX_SIZE {
+        rotate(path)?;
+    }
+
+    let mut file = OpenOptions::new().create(true).append(true).open(path).map_err(|e|e.to_string())?;
+    writeln!(file, "{}", line).map_err(|e|e.to_string())
+}
+
+fn spawn_writer(path: PathBuf) -> Sender<String> {
+    //! Starts thread, which appends received lines to traffic log for as long as gateway runs.
+    let (sender, receiver) = mpsc::channel::<String>();
+    thread::spawn(move || for line in receiver {
+        if let Err(error) = append_line(&path, &line) {
+            println!("[-] Failed to write traffic log {}: {}", path.display(), error);
+        }
+    });
+    sender
+}
+
+pub fn record(journal_entry: &JournalEntry) {
+    //! Queues handled exchange to be appended as one JSON line into `ROCKERY_TRAFFIC_LOG`
+    //! file, if configured. Writing happens in background, so it never delays the response.
+    if let Some(sender) = &*TRAFFIC_LOG_SENDER {
+        // Writer thread never exits, so sending cannot fail.
+        let _ = sender.send(journal_entry.as_json().to_string());
+    }
+}
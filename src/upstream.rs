@@ -0,0 +1,124 @@
+use std::error::Error as StdError;
+use std::io;
+use std::sync::Arc;
+
+use hyper::{Body, Client, Request, Response};
+use hyper::client::HttpConnector;
+use hyper::http::StatusCode;
+use hyper_rustls::HttpsConnector;
+use lazy_static::lazy_static;
+use rustls::ClientConfig;
+use serde_json::json;
+
+use crate::settings::{self, UpstreamHttp2};
+use crate::response::HTTPResponse;
+use crate::tls::client_config;
+
+/// HTTP client used for all requests sent to target. Speaks both `http` and `https`.
+pub type UpstreamClient = Client<HttpsConnector<HttpConnector>>;
+
+lazy_static! {
+    /// TLS configuration for connections to target. Certificates are loaded only once.
+    pub static ref UPSTREAM_TLS_CONFIG: Arc<ClientConfig> = Arc::new(
+        client_config().unwrap_or_else(|error| panic!("Invalid target TLS configuration: {}", error))
+    );
+
+    /// Client shared by all proxied requests, so keep-alive connections to target are reused.
+    pub static ref UPSTREAM_CLIENT: UpstreamClient = build_client();
+}
+
+/// Reason, why exchange with target failed.
+pub enum UpstreamError {
+    /// Connection to target could not be established.
+    ConnectFailed(String),
+    /// Target did not respond in time.
+    Timeout(String),
+    /// Connection was closed or reset by target.
+    ConnectionReset(String),
+    /// Any other failure, e.g. invalid response.
+    Other(String),
+}
+
+impl UpstreamError {
+    fn from_hyper_error(error: &hyper::Error) -> UpstreamError {
+        //! Classifies error by its kind and by underlying IO error.
+        let mut io_error_kind = None;
+        let mut source = error.source();
+        while let Some(cause) = source {
+            if let Some(io_error) = cause.downcast_ref::<io::Error>() {
+                io_error_kind = Some(io_error.kind());
+                break;
+            }
+            source = cause.source();
+        }
+
+        let message = error.to_string();
+        match io_error_kind {
+            Some(io::ErrorKind::TimedOut) => UpstreamError::Timeout(message),
+            _ if error.is_connect() => UpstreamError::ConnectFailed(message),
+            Some(io::ErrorKind::ConnectionReset) | Some(io::ErrorKind::ConnectionAborted)
+                | Some(io::ErrorKind::BrokenPipe) => UpstreamError::ConnectionReset(message),
+            _ if error.is_incomplete_message() || error.is_closed() => UpstreamError::ConnectionReset(message),
+            _ => UpstreamError::Other(message),
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        //! Returns machine readable code of error.
+        match self {
+            UpstreamError::ConnectFailed(_) => "connect_failed",
+            UpstreamError::Timeout(_) => "timeout",
+            UpstreamError::ConnectionReset(_) => "connection_reset",
+            UpstreamError::Other(_) => "upstream_error",
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        //! Returns human readable description of error.
+        match self {
+            UpstreamError::ConnectFailed(error) => format!("Cannot connect to target: {}", error),
+            UpstreamError::Timeout(error) => format!("Target did not respond in time: {}", error),
+            UpstreamError::ConnectionReset(error) => format!("Connection was reset by target: {}", error),
+            UpstreamError::Other(error) => format!("Request to target failed: {}", error),
+        }
+    }
+
+    pub fn as_http_response(&self) -> HTTPResponse {
+        //! Converts error into `504 Gateway Timeout` response.
+        HTTPResponse{
+            status_code: StatusCode::GATEWAY_TIMEOUT,
+            body: json!({
+                "msg": self.describe(),
+                "error": self.code(),
+            }).to_string()
+        }
+    }
+}
+
+pub fn build_client() -> UpstreamClient {
+    //! Creates new client for requests sent to target. HTTP/2 connections are
+    //! negotiated by TLS connector, unless HTTP/2 is spoken with prior knowledge.
+    let mut http_connector = HttpConnector::new();
+    http_connector.enforce_http(false);
+    http_connector.set_connect_timeout(*settings::UPSTREAM_CONNECT_TIMEOUT);
+
+    Client::builder()
+        .pool_idle_timeout(*settings::UPSTREAM_POOL_IDLE_TIMEOUT)
+        .http2_only(*settings::UPSTREAM_HTTP2 == UpstreamHttp2::PriorKnowledge)
+        .build(
+            HttpsConnector::from((http_connector, UPSTREAM_TLS_CONFIG.clone()))
+        )
+}
+
+pub async fn send(request: Request<Body>) -> Result<Response<Body>, UpstreamError> {
+    //! Sends request to target using shared client. Fails if response
+    //! headers are not received within `UPSTREAM_TOTAL_TIMEOUT`.
+    let response = UPSTREAM_CLIENT.request(request);
+    let result = match *settings::UPSTREAM_TOTAL_TIMEOUT {
+        Some(total_timeout) => tokio::time::timeout(total_timeout, response).await.map_err(
+            |_| UpstreamError::Timeout(format!("no response within {} ms", total_timeout.as_millis()))
+        )?,
+        None => response.await,
+    };
+    result.map_err(|error| UpstreamError::from_hyper_error(&error))
+}
@@ -1,22 +1,38 @@
-use std::{env::set_var, net::SocketAddr};
+use core::str::FromStr;
+use std::{env, net::SocketAddr};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use futures::executor;
 use tokio::net;
-use hyper::{Body, Request, body::to_bytes};
-use serde_json::Value;
+
+use crate::errors::FieldError;
+use crate::settings;
+use hyper::{Body, Request, HeaderMap, body::to_bytes};
+use hyper::header::HOST;
+use hyper::http::uri::{Authority, PathAndQuery, Scheme, Uri};
+use serde_json::{json, Value};
 
 pub fn set_env_vars(){
-    //! Sets environment variables `ROCKERY_HOST`, `ROCKERY_PORT`, `TARGET_HOST`, `TARGET_PORT`.
-    //! This is for development purposes. In production, these will be set by docker.
-    
-    // Temporary solution
-    set_var("ROCKERY_HOST", "127.0.0.1");
-    set_var("ROCKERY_PORT", "3000");
+    //! Sets defaults of environment variables `ROCKERY_HOST`, `ROCKERY_PORT`, `TARGET_HOST`, `TARGET_PORT`
+    //! and `SPOOF_HOST_HEADER`. This is for development purposes. Variables, which are already set (e.g. by
+    //! docker), are kept.
+    set_default_var("ROCKERY_HOST", "127.0.0.1");
+    set_default_var("ROCKERY_PORT", "3000");
 
-    set_var("TARGET_HOST", "127.0.0.1");
-    set_var("TARGET_PORT", "5000");
+    set_default_var("TARGET_HOST", "127.0.0.1");
+    // `TARGET_PORT` of https target defaults to 443 in settings.
+    if !env::var("TARGET_SCHEME").is_ok_and(|scheme| scheme.eq_ignore_ascii_case("https")) {
+        set_default_var("TARGET_PORT", "5000");
+    }
 
-    set_var("SPOOF_HOST_HEADER", "1");
+    set_default_var("SPOOF_HOST_HEADER", "1");
+}
+
+fn set_default_var(name: &str, value: &str){
+    //! Sets environment variable, unless it is already set.
+    if env::var_os(name).is_none() {
+        env::set_var(name, value);
+    }
 }
 
 pub fn resolve_to_socket_address(hostname: &str, port: &u16) -> SocketAddr{
@@ -63,5 +79,66 @@ pub async fn parse_http_body_to_json(req: Request<Body>) -> Result<Value, String
 }
 
 pub fn json_message(message: &str) -> String{
-    format!("{{\"msg\": \"{}\" }}\r\n", message)
+    //! Serializes `{"msg": message}` response body.
+    json!({"msg": message}).to_string()
+}
+
+pub fn get_request_host(uri: &Uri, headers: &HeaderMap) -> Option<String>{
+    //! Returns lowercase hostname request is addressed to, without port. Absolute URI
+    //! (used when Rockery acts as a forward proxy) takes precedence over `Host` header.
+    let host = match uri.host() {
+        Some(host) => host.to_owned(),
+        None => Authority::from_str(headers.get(HOST)?.to_str().ok()?).ok()?.host().to_owned(),
+    };
+    if host.is_empty() { None } else { Some(host.to_lowercase()) }
+}
+
+pub fn headers_to_json(headers: &HeaderMap) -> Value{
+    //! Serializes headers into JSON array of `{"name": ..., "value": ...}` objects.
+    //! Array is used instead of object, so repeated headers are preserved.
+    Value::Array(
+        headers.iter().map(
+            |(name, value)| json!({
+                "name": name.as_str(),
+                "value": String::from_utf8_lossy(value.as_bytes()),
+            })
+        ).collect()
+    )
+}
+
+pub fn unix_timestamp_millis() -> i64{
+    //! Returns count of milliseconds elapsed since unix epoch.
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as i64).unwrap_or(0)
+}
+
+pub fn build_target_uri(path_and_query: PathAndQuery) -> Uri{
+    //! Builds URI pointing to `path_and_query` on target. For `https` target, hostname is used
+    //! instead of resolved address, as it is needed for certificate verification.
+    let authority = if *settings::TARGET_SCHEME == Scheme::HTTPS {
+        format!("{}:{}", *settings::TARGET_HOST, *settings::TARGET_PORT)
+    } else {
+        settings::TARGET_SOCKET_ADDRESS.to_string()
+    };
+    Uri::builder()
+        .scheme(settings::TARGET_SCHEME.clone())
+        .authority(&*authority)
+        .path_and_query(path_and_query)
+        .build()
+        .unwrap()
+}
+
+pub fn get_json_optional_string(
+    parsed_json: &Value,
+    field_name: &str,
+    error_messages: &mut Vec<FieldError>
+) -> Option<String>{
+    //! Extracts and validates optional string field.
+    match parsed_json.get(field_name){
+        Some(Value::Null) | None => None,
+        Some(Value::String(value)) => Some(value.to_owned()),
+        Some(_) => {
+            error_messages.push(FieldError::new(field_name, format!("{} must be a string", field_name)));
+            None
+        }
+    }
 }
@@ -1,50 +1,86 @@
 use core::str::FromStr;
 use std::convert::From;
+use std::time::Instant;
 
-use hyper::{ Body, Request, Response, Client, header::HeaderValue, Method };
-use hyper::http::uri::{Scheme, Uri};
+use hyper::{ Body, Request, Response, header::{self, HeaderValue}, Method };
+use futures::future::try_join;
+use hyper::http::request::Parts as RequestParts;
+use tokio_tungstenite::tungstenite::handshake::server::create_response;
 use hyper::http::StatusCode;
-use serde_json::{ser, Value as JsonValue};
+use serde_json::{json, ser, Value as JsonValue};
 
 use crate::settings;
-use crate::utils::{is_json_request, json_message, parse_http_body_to_json, parse_http_body_to_string};
+use crate::utils::{
+    get_json_optional_string, get_request_host, headers_to_json, is_json_request, json_message, parse_http_body_to_json,
+    unix_timestamp_millis
+};
 use crate::response::HTTPResponse;
-use crate::db::MockingRule;
+use crate::errors::{ApiError, DbError, FieldError};
+use crate::db::{ANY_METHOD, GraphqlRule, GrpcRule, JournalEntry, MockingRule, PatchRule, Record, RewriteRule, RoutingRule, WebSocketRule};
+use crate::diagnostics::find_near_misses;
+use crate::har::{har_to_mocking_rules, journal_to_har};
+use crate::traffic_log;
+use crate::upstream;
+use crate::routing::{resolve_target, routing_rule_from_json};
+use crate::patching::{find_patch_rules, patch_response_body, patch_response_head, patch_rule_from_json, patches_body};
+use crate::rewriting::{find_rewrite_rules, rewrite_request, rewrite_response_headers, rewrite_rule_from_json};
+use crate::proxy::{intercept, tunnel};
+use crate::forwarding::{accepts_trailers, add_forwarding_headers, restore_host_header, strip_hop_by_hop_headers, ClientConnection};
+use crate::websocket::{mock_conversation, splice, websocket_rule_from_json};
+use crate::grpc::{
+    decode_request_data as decode_grpc_request_data, grpc_rule_from_json, mock_response as mock_grpc_response, parse_grpc_path
+};
+use crate::graphql::{
+    find_matching_rule as find_matching_graphql_rule, graphql_rule_from_json, mock_response as mock_graphql_response,
+    parse_operation as parse_graphql_operation
+};
+use crate::forms::{form_matches, get_json_request_files, get_json_request_form, parse_form_body};
+use crate::xml::{
+    get_json_request_namespaces, get_json_request_xpath, get_json_response_xml, validate_xpath_prefixes, xml_matches, XML_CONTENT_TYPE
+};
+use crate::cors::{
+    apply_cors_headers, apply_mock_cors_headers, get_json_response_cors, get_requested_method, is_preflight,
+    preflight_response as cors_preflight_response, resolve_policy
+};
+use crate::streaming::{chunks_to_data, get_json_response_chunks, stream_chunks, EVENT_STREAM_CONTENT_TYPE};
+use crate::body::{body_to_data, capture_body, read_body_bytes};
 
 /// View for handling mocking rules, which should
 /// be called statically only. Initializing function
-/// is `create`, `delete` and `default`.
+/// is `create`, `delete`, `import_har` and `default`.
 pub struct RuleView {}
 impl RuleView {
     fn get_json_request_url(
         parsed_json: &JsonValue,
-        error_messages: &mut Vec<String>
+        error_messages: &mut Vec<FieldError>
     ) -> Option<String>{
         //! Extracts and validates request url. 
         let field_name = "_rockery_request_url".to_string();
         let mut output : Option<String> = None;
 
         match parsed_json.get(&field_name){
-            Some(field) if !field.is_string() => error_messages.push(
+            Some(field) if !field.is_string() => error_messages.push(FieldError::new(
+                &field_name,
                 format!("{} must be a string", field_name)
-            ),
+            )),
             Some(field) => output = Some(field.as_str().unwrap_or_else(||"/").to_owned()),
-            None => error_messages.push(format!("Field {} is required", field_name))
+            None => error_messages.push(FieldError::new(&field_name, format!("Field {} is required", field_name)))
         };
         output
     }
 
     fn get_json_request_query(
         parsed_json: &JsonValue,
-        error_messages: &mut Vec<String>
+        error_messages: &mut Vec<FieldError>
     ) -> Option<String>{
         let field_name = "_rockery_request_query".to_owned();
         let mut output : Option<String> = None;
 
         match parsed_json.get(&field_name){
-            Some(field) if !field.is_string() => error_messages.push(
+            Some(field) if !field.is_string() => error_messages.push(FieldError::new(
+                &field_name,
                 format!("{} must be a string", field_name)
-            ),
+            )),
             Some(field) => output = match field.as_str() {
                 Some(query) => Some(query.to_owned()),
                 None => None
@@ -54,46 +90,67 @@ impl RuleView {
         output
     }
 
+    fn get_json_request_host(
+        parsed_json: &JsonValue,
+        error_messages: &mut Vec<FieldError>
+    ) -> Option<String>{
+        //! Extracts and validates optional request host. Rules without host match any host.
+        let field_name = "_rockery_request_host".to_owned();
+
+        match parsed_json.get(&field_name){
+            Some(field) if !field.is_string() => {
+                error_messages.push(FieldError::new(&field_name, format!("{} must be a string", field_name)));
+                None
+            },
+            Some(field) => field.as_str().map(|host| host.to_lowercase()),
+            None => None
+        }
+    }
+
     fn get_json_request_method(
         parsed_json: &JsonValue,
-        error_messages: &mut Vec<String>
+        error_messages: &mut Vec<FieldError>
     ) -> Option<Method>{
         let field_name = "_rockery_request_method".to_owned();
         let mut output : Option<Method> = None;
 
         match parsed_json.get(&field_name){
             Some(field) if field.is_string() => {
-                let error_msg : String = format!(
-                    "{} must be one of following: {}",
-                    field_name,
-                    settings::INTERCEPTABLE_METHODS
-                        .iter()
-                        .map(|m| m.to_string())
-                        .collect::<Vec<String>>()
-                        .join(", ")
-                );
+                let error_msg : String = match settings::INTERCEPTABLE_METHODS.as_ref() {
+                    Some(methods) => format!(
+                        "{} must be one of following: {}, {}",
+                        field_name,
+                        methods
+                            .iter()
+                            .map(|m| m.to_string())
+                            .collect::<Vec<String>>()
+                            .join(", "),
+                        ANY_METHOD
+                    ),
+                    None => format!("{} must be a valid method other than CONNECT", field_name),
+                };
                 match Method::from_str(
                     &(field.as_str().unwrap_or("").to_uppercase())
                 ) {
                     Ok(method) => {
-                        if !settings::INTERCEPTABLE_METHODS.contains(&method){
-                            error_messages.push(error_msg)
+                        if method.as_str() != ANY_METHOD && !settings::is_interceptable(&method){
+                            error_messages.push(FieldError::new(&field_name, error_msg))
                         }else{
                             output = Some(method)
                         }
                     },
-                    Err(_) => error_messages.push(error_msg),
+                    Err(_) => error_messages.push(FieldError::new(&field_name, error_msg)),
                 };
             },
-            Some(_field) => error_messages.push(format!("{} must be a string", field_name)),
-            None => error_messages.push(format!("Field {} is missing", field_name)),
+            Some(_field) => error_messages.push(FieldError::new(&field_name, format!("{} must be a string", field_name))),
+            None => error_messages.push(FieldError::new(&field_name, format!("Field {} is missing", field_name))),
         }
         output
     }
 
     fn get_json_request_data(
         parsed_json: &JsonValue,
-        error_messages: &mut Vec<String>
+        error_messages: &mut Vec<FieldError>
     ) -> Option<String>{
         let field_name = "_rockery_request_data".to_owned();
         let mut output : Option<String> = None;
@@ -102,11 +159,12 @@ impl RuleView {
             Some(field) => {
                 match ser::to_string(&field){
                     Ok(serialized_data) => output = Some(serialized_data),
-                    Err(_) => error_messages.push(
+                    Err(_) => error_messages.push(FieldError::new(
+                        &field_name,
                         format!(
                             "{} must be of JSON format in order to be serialized properly", field_name
                         )
-                    )
+                    ))
                 };
             },
             None => ()
@@ -116,7 +174,7 @@ impl RuleView {
 
     fn get_json_response_status_code(
         parsed_json: &JsonValue,
-        error_messages: &mut Vec<String>
+        error_messages: &mut Vec<FieldError>
     ) -> Option<i64>{
         let field_name = "_rockery_response_status_code".to_owned();
         let mut output : Option<i64> = None;
@@ -125,27 +183,25 @@ impl RuleView {
             Some(field) => {
                 match field.as_i64() {
                     // Let's allow totally custom status codes for testing purposes...
-                    Some(status_code) if StatusCode::from_u16(status_code as u16).is_err() => error_messages.push(
-                        json_message(
-                            &format!("Provided {} status code os not valid http status code", field_name)
-                        )
-                    ),
+                    Some(status_code) if StatusCode::from_u16(status_code as u16).is_err() => error_messages.push(FieldError::new(
+                        &field_name,
+                        format!("Provided {} status code os not valid http status code", field_name)
+                    )),
                     Some(status_code) => output = Some(status_code),
-                    None => error_messages.push(
-                        json_message(
-                            &format!("{} must be an integer", field_name)
-                        )
-                    )
+                    None => error_messages.push(FieldError::new(
+                        &field_name,
+                        format!("{} must be an integer", field_name)
+                    ))
                 }
             },
-            None => error_messages.push(format!("Field {} is required", field_name)),
+            None => error_messages.push(FieldError::new(&field_name, format!("Field {} is required", field_name))),
         }
         output
     }
 
     fn get_json_response_data(
         parsed_json: &JsonValue,
-        error_messages: &mut Vec<String>
+        error_messages: &mut Vec<FieldError>
     ) -> Option<String> {
         let field_name = "_rockery_response_data".to_owned();
         let mut output : Option<String> = None;
@@ -154,33 +210,106 @@ impl RuleView {
             Some(field) => {
                 match ser::to_string(&field){
                     Ok(serialized_data) => output = Some(serialized_data),
-                    Err(_) => error_messages.push(
+                    Err(_) => error_messages.push(FieldError::new(
+                        &field_name,
                         format!("{} must be of JSON format in order to be serialized properly", field_name)
-                    )
+                    ))
                 };
             },
-            None => error_messages.push(format!("Field {} is required", field_name)),
+            None => error_messages.push(FieldError::new(&field_name, format!("Field {} is required", field_name))),
         }
         output
     }
 
-    fn create_mocking_rule_from_json(parsed_body: &JsonValue) -> Result<MockingRule, String>{
-        //! Creates `MockingRule` from provided `serde_json:Value` or returns error message if validation fails.
+    fn get_json_response_sse(
+        parsed_json: &JsonValue,
+        error_messages: &mut Vec<FieldError>
+    ) -> bool {
+        //! Extracts and validates optional flag streaming chunks as Server-Sent Events.
+        let field_name = "_rockery_response_sse".to_owned();
+
+        match parsed_json.get(&field_name){
+            Some(JsonValue::Bool(sse)) => {
+                if *sse && parsed_json.get("_rockery_response_chunks").is_none() {
+                    error_messages.push(FieldError::new(&field_name, format!("{} requires _rockery_response_chunks", field_name)));
+                }
+                *sse
+            },
+            Some(JsonValue::Null) | None => false,
+            Some(_) => {
+                error_messages.push(FieldError::new(&field_name, format!("{} must be a boolean", field_name)));
+                false
+            }
+        }
+    }
+
+    fn create_mocking_rule_from_json(parsed_body: &JsonValue) -> Result<MockingRule, Vec<FieldError>>{
+        //! Creates `MockingRule` from provided `serde_json:Value` or returns error messages if validation fails.
         //! Just creates instance of `MockingRule` with filled attributes, but does not save to db!!! You need to
         //! call `MockingRule`'s `save` method in order to perform database save! 
 
         // let mut missing_fields : Vec<String> = vec![];
-        let mut error_messages : Vec<String> = vec![];
+        let mut error_messages : Vec<FieldError> = vec![];
 
         // Gather information about to-be-mocked request.
         let request_url : Option<String> = Self::get_json_request_url(parsed_body, &mut error_messages);
         let request_query : Option<String> = Self::get_json_request_query(parsed_body, &mut error_messages);
         let request_method : Option<Method> = Self::get_json_request_method(parsed_body, &mut error_messages);
         let request_data : Option<String> = Self::get_json_request_data(parsed_body, &mut error_messages);
+        let request_form : Option<String> = get_json_request_form(parsed_body, "_rockery_request_form", &mut error_messages);
+        let request_files : Option<String> = get_json_request_files(parsed_body, "_rockery_request_files", &mut error_messages);
+        let request_xpath : Option<String> = get_json_request_xpath(parsed_body, "_rockery_request_xpath", &mut error_messages);
+        let request_namespaces : Option<String> = get_json_request_namespaces(
+            parsed_body, "_rockery_request_namespaces", &mut error_messages
+        );
+        let request_soap_action : Option<String> = get_json_optional_string(
+            parsed_body, "_rockery_request_soap_action", &mut error_messages
+        );
+        if request_data.is_some() && (request_form.is_some() || request_files.is_some() || request_xpath.is_some()) {
+            error_messages.push(FieldError::new(
+                "_rockery_request_data",
+                "_rockery_request_data cannot be combined with _rockery_request_form, _rockery_request_files or _rockery_request_xpath".to_owned()
+            ));
+        }
+        if (request_form.is_some() || request_files.is_some()) && request_xpath.is_some() {
+            error_messages.push(FieldError::new("_rockery_request_xpath", "_rockery_request_xpath cannot be combined with _rockery_request_form or _rockery_request_files".to_owned()));
+        }
+        validate_xpath_prefixes(&request_xpath, &request_namespaces, "_rockery_request_xpath", &mut error_messages);
+        if request_namespaces.is_some() && request_xpath.is_none() {
+            error_messages.push(FieldError::new("_rockery_request_namespaces", "_rockery_request_namespaces requires _rockery_request_xpath".to_owned()));
+        }
+        let request_host : Option<String> = Self::get_json_request_host(parsed_body, &mut error_messages);
 
         // Gather information about how to respond to to-be-mocked requests.
         let response_status_code : Option<i64> = Self::get_json_response_status_code(parsed_body, &mut error_messages);
-        let response_data : Option<String> = Self::get_json_response_data(parsed_body, &mut error_messages);
+        let response_chunks : Option<String> = get_json_response_chunks(
+            parsed_body, "_rockery_response_chunks", &mut error_messages
+        );
+        let response_sse : bool = Self::get_json_response_sse(parsed_body, &mut error_messages);
+        let response_cors : Option<String> = get_json_response_cors(parsed_body, "_rockery_response_cors", &mut error_messages);
+        let response_xml : Option<String> = get_json_response_xml(parsed_body, "_rockery_response_xml", &mut error_messages);
+        let response_data : Option<String> = if parsed_body.get("_rockery_response_chunks").is_some() {
+            if parsed_body.get("_rockery_response_data").is_some() || response_xml.is_some() {
+                error_messages.push(FieldError::new(
+                    "_rockery_response_data",
+                    "_rockery_response_data and _rockery_response_xml cannot be combined with _rockery_response_chunks".to_owned()
+                ));
+            }
+            None
+        } else if response_xml.is_some() {
+            if parsed_body.get("_rockery_response_data").is_some() {
+                error_messages.push(FieldError::new("_rockery_response_data", "_rockery_response_data cannot be combined with _rockery_response_xml".to_owned()));
+            }
+            response_xml.clone()
+        } else {
+            Self::get_json_response_data(parsed_body, &mut error_messages)
+        };
+        let response_content_type : Option<String> = get_json_optional_string(
+            parsed_body, "_rockery_response_content_type", &mut error_messages
+        ).or_else(|| response_xml.map(|_| XML_CONTENT_TYPE.to_owned()));
+        if response_content_type.as_ref().is_some_and(|content_type| HeaderValue::from_str(content_type).is_err()) {
+            error_messages.push(FieldError::new("_rockery_response_content_type", "_rockery_response_content_type must be a valid header value".to_owned()));
+        }
         
         // Create mocking rule if possible.
         match (request_method, request_url, response_status_code, error_messages.len() > 0) {
@@ -188,30 +317,32 @@ impl RuleView {
                 MockingRule {
                     id: None,
                     request_method: request_method_str,
+                    request_host,
                     request_url: request_url_str,
                     request_query: request_query,
                     request_data: request_data,
+                    request_form,
+                    request_files,
+                    request_xpath,
+                    request_namespaces,
+                    request_soap_action,
                     response_status_code: response_status_code_int,
                     response_data: response_data,
+                    response_content_type,
+                    response_chunks,
+                    response_sse,
+                    response_cors,
                 }
             ),
-            (_, _, _, true) => match serde_json::to_string(&error_messages) {
-                Ok(error_message) => Err(error_message),
-                Err(_) => Err("Fatal Error. Serialization of error messages failed!".to_owned()),
-            },
-            (_, _, _, false) => Err("Internal error. One of fields do not handle error messages properly".to_owned())
+            // Every required field reports error when it is missing.
+            _ => Err(error_messages),
         }        
     }
 
-    fn validate_rule_request(req: &Request<Body>) -> Result<(), HTTPResponse>{
+    fn validate_rule_request(req: &Request<Body>) -> Result<(), ApiError>{
         //! Validates if HTTP request fill needed general requirements - It is parseable, properly encoded, etc.
         if !is_json_request(&req){
-            return Err(
-                HTTPResponse{
-                    body: json_message("Request Content-Type header must be application/json"),
-                    status_code: StatusCode::BAD_REQUEST
-                }
-            );
+            return Err(ApiError::UnsupportedMediaType);
         }
         Ok(())
     }
@@ -220,20 +351,9 @@ impl RuleView {
         //! Hnadles requests, which attempt to create a new mocking rule.
 
         Self::validate_rule_request(&req)?;
-        let parsed_body: JsonValue = parse_http_body_to_json(req).await.map_err(
-            |error|
-                HTTPResponse{
-                    body: json_message(&error.to_string()),
-                    status_code: StatusCode::UNPROCESSABLE_ENTITY
-                }
-        )?;
+        let parsed_body: JsonValue = parse_http_body_to_json(req).await.map_err(ApiError::MalformedBody)?;
         
-        let mut new_rule = Self::create_mocking_rule_from_json(&parsed_body).map_err(|error|
-            HTTPResponse{
-                body: json_message(&error),
-                status_code: StatusCode::UNPROCESSABLE_ENTITY
-            }
-        )?;
+        let mut new_rule = Self::create_mocking_rule_from_json(&parsed_body).map_err(ApiError::Validation)?;
         match new_rule.create(){
             Ok(_) => {
                 return Ok(
@@ -248,183 +368,1018 @@ impl RuleView {
                     ),
                 }).as_hyper_response()
             )},
-            Err(error_msg) => Err(
-                HTTPResponse{
-                    status_code: StatusCode::INTERNAL_SERVER_ERROR,
-                    body: json_message(&error_msg),
-                }
-            )
+            Err(error) => Err(ApiError::from(error).into())
         }
     }
     pub async fn delete(req: Request<Body>) -> Result<Response<Body>, HTTPResponse> {
         //! Hnadles requests, which attempt to delete existing mocking rule.
         Self::validate_rule_request(&req)?;
-        let parsed_body: JsonValue = parse_http_body_to_json(req).await.map_err(
-            |error|
-                HTTPResponse{
-                    status_code: StatusCode::UNPROCESSABLE_ENTITY,
-                    body: json_message(&error)
-                }
-        )?;
+        let parsed_body: JsonValue = parse_http_body_to_json(req).await.map_err(ApiError::MalformedBody)?;
 
-        let mut error_messages : Vec<String> = vec![];
+        let mut error_messages : Vec<FieldError> = vec![];
 
         let request_url : Option<String> = Self::get_json_request_url(&parsed_body, &mut error_messages);
         let request_query : Option<String> = Self::get_json_request_query(&parsed_body, &mut error_messages);
         let request_method : Option<Method> = Self::get_json_request_method(&parsed_body, &mut error_messages);
         let request_data : Option<String> = Self::get_json_request_data(&parsed_body, &mut error_messages);
+        let request_form : Option<String> = get_json_request_form(&parsed_body, "_rockery_request_form", &mut error_messages);
+        let request_files : Option<String> = get_json_request_files(&parsed_body, "_rockery_request_files", &mut error_messages);
+        let request_xpath : Option<String> = get_json_request_xpath(&parsed_body, "_rockery_request_xpath", &mut error_messages);
+        let request_namespaces : Option<String> = get_json_request_namespaces(
+            &parsed_body, "_rockery_request_namespaces", &mut error_messages
+        );
+        let request_soap_action : Option<String> = get_json_optional_string(
+            &parsed_body, "_rockery_request_soap_action", &mut error_messages
+        );
+        let request_host : Option<String> = Self::get_json_request_host(&parsed_body, &mut error_messages);
         
         if error_messages.len() > 0{
-            let (status_code, serialized_errors) = match serde_json::to_string(&error_messages){
-                Ok(serialized_errors) => (StatusCode::UNPROCESSABLE_ENTITY, serialized_errors),
-                Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, json_message("Fatal Error: Failed to serialize errors")),
-            };
-
-            return Err(
-                HTTPResponse{
-                    status_code: status_code,
-                    body: json_message(&serialized_errors)
-                }
-            );
+            return Err(ApiError::Validation(error_messages).into());
         }
         
         let mut found_rules = MockingRule::find(
             &request_url,
             &request_query,
             &request_method,
-            &request_data
-        ).map_err(
-            |e| HTTPResponse{
-                status_code: StatusCode::INTERNAL_SERVER_ERROR,
-                body: json_message(&e.to_string())
-            }
-        )?;
+            &request_data,
+            &request_host
+        ).map_err(ApiError::from)?;
+        found_rules.retain(
+            |rule| rule.request_form == request_form
+                && rule.request_files == request_files
+                && rule.request_xpath == request_xpath
+                && rule.request_namespaces == request_namespaces
+                && rule.request_soap_action == request_soap_action
+        );
 
         if found_rules.len() == 0 {
-            return Err(
-                HTTPResponse{
-                    status_code: StatusCode::NOT_FOUND,
-                    body: json_message("Mocking rule does not exists and has not been deleted.")
-                }
-            )
+            return Err(ApiError::NotFound("Mocking rule does not exists and has not been deleted.".to_owned()).into());
         }
-        match found_rules[0].delete() {
-            Ok(_) => Ok(
-                (HTTPResponse{
-                    status_code: StatusCode::OK,
-                    body: json_message("A rule has been deleted successfully")
-                }).as_hyper_response()
-            ),
-            Err(error) => Err(
-                HTTPResponse{
-                    status_code: StatusCode::INTERNAL_SERVER_ERROR,
-                    body: json_message(&error)
-                }
-            )
+        found_rules[0].delete().map_err(ApiError::from)?;
+        Ok(
+            (HTTPResponse{
+                status_code: StatusCode::OK,
+                body: json_message("A rule has been deleted successfully")
+            }).as_hyper_response()
+        )
+    }
+
+    pub async fn import_har(req: Request<Body>) -> Result<Response<Body>, HTTPResponse> {
+        //! Handles requests, which create mocking rules from entries of uploaded HAR document.
+        //! Entries, which cannot be converted or already have a rule, are skipped and reported.
+        Self::validate_rule_request(&req)?;
+        let parsed_body: JsonValue = parse_http_body_to_json(req).await.map_err(ApiError::MalformedBody)?;
+
+        let new_rules = har_to_mocking_rules(&parsed_body).map_err(
+            |error| ApiError::Validation(vec![FieldError::new("log.entries", error)])
+        )?;
+
+        let mut created_rule_ids : Vec<i64> = vec![];
+        let mut skipped_entries : Vec<JsonValue> = vec![];
+        for (index, new_rule) in new_rules.into_iter().enumerate() {
+            let created = new_rule.and_then(|mut new_rule| {
+                new_rule.create()?;
+                Ok(new_rule)
+            });
+            match created {
+                Ok(new_rule) => created_rule_ids.extend(new_rule.id),
+                Err(error) => skipped_entries.push(json!({"index": index, "reason": error})),
+            }
+        }
+
+        Ok(
+            (HTTPResponse{
+                status_code: if created_rule_ids.is_empty() { StatusCode::OK } else { StatusCode::CREATED },
+                body: json!({
+                    "msg": format!(
+                        "{} rules have been created, {} entries have been skipped",
+                        created_rule_ids.len(),
+                        skipped_entries.len()
+                    ),
+                    "created_rule_ids": created_rule_ids,
+                    "skipped": skipped_entries,
+                }).to_string(),
+            }).as_hyper_response()
+        )
+    }
+
+    fn record_journal_entry(mut journal_entry: JournalEntry) -> Option<i64>{
+        //! Saves `journal_entry` into journal and traffic log, returning its id. Failure
+        //! is only reported, since it must never affect the response being returned.
+        if let Err(error) = journal_entry.create(){
+            println!("[-] Failed to record request into journal: {}", error);
+        }
+        traffic_log::record(&journal_entry);
+        journal_entry.id
+    }
+
+    fn log_near_misses(journal_entry: &JournalEntry){
+        //! Logs rules closest to request, which did not match any rule.
+        match find_near_misses(
+            &journal_entry.request_url,
+            &journal_entry.request_query,
+            &journal_entry.request_method,
+            &journal_entry.request_data,
+            &journal_entry.request_host
+        ) {
+            Ok(near_misses) => for near_miss in near_misses {
+                println!(
+                    "[?] Near miss with rule #{}: {}",
+                    near_miss.rule_id.map_or("<None>".to_owned(), |id| id.to_string()),
+                    near_miss.describe()
+                );
+            },
+            Err(error) => println!("[-] Failed to compute near misses: {}", error),
         }
     }
 
     pub async fn default(req: Request<Body>) -> Result<Response<Body>, HTTPResponse> {
         //! Hnadles requests, which will be possibly resent to target, waits
-        //! for response, and returns the response.
-        
-        // TODO: Optimize!!!
-        let req_uri = req.uri().clone();
-        let method = req.method().clone();
-        let headers = req.headers().clone();
-        let http_version = req.version().clone();
-
-
-        let request_body : String = parse_http_body_to_string(req).await.map_err(
-            |error|
-                HTTPResponse{
-                    status_code: StatusCode::UNPROCESSABLE_ENTITY,
-                    body: json_message(&error)
-                }
-        )?;
+        //! for response, and returns the response. Request body is buffered
+        //! only when some rule matches on it, otherwise bodies are streamed.
+        let started_at = unix_timestamp_millis();
+        let timer = Instant::now();
+
+        let (parts, body) = req.into_parts();
+        let client = parts.extensions.get::<ClientConnection>().copied();
+        let req_uri = parts.uri;
+        let method = parts.method;
+        let headers = parts.headers;
+        let http_version = parts.version;
+
+        // Requests sent to forward proxy have absolute URI. Only path and query are matched against rules.
+        let request_url = req_uri.path_and_query().map_or("/".to_owned(), |path_and_query| path_and_query.to_string());
+        let request_query = req_uri.query().map(|o|o.to_owned());
+        let request_host = get_request_host(&req_uri, &headers);
+        let request_origin = headers.get(header::ORIGIN).and_then(|origin| origin.to_str().ok()).map(|origin| origin.to_owned());
+        let interceptable = settings::is_interceptable(&method);
+
+        let graphql_rules = if interceptable {
+            GraphqlRule::find_by_path(req_uri.path()).map_err(ApiError::from)?
+        } else {
+            vec![]
+        };
+
+        // GraphQL operations are sent in body of POST requests.
+        let requires_body = interceptable && (
+            (method == Method::POST && !graphql_rules.is_empty()) || MockingRule::requires_body(
+                &request_url,
+                &request_query,
+                &method,
+                &request_host
+            ).map_err(ApiError::from)?
+        );
+
+        let mut streamed_body = None;
+        let mut buffered_body = None;
+        if requires_body {
+            buffered_body = Some(
+                read_body_bytes(body, *settings::MAX_BUFFERED_BODY).await.map_err(ApiError::from)?
+            );
+        } else {
+            streamed_body = Some(body);
+        }
+        let request_data = buffered_body.as_ref().and_then(|body|body_to_data(body));
+        let request_form = buffered_body.as_ref().and_then(|body|parse_form_body(&headers, body));
+
+        let mut journal_entry = JournalEntry {
+            id: None,
+            started_at,
+            duration_ms: 0,
+            request_method: method.clone(),
+            request_host: request_host.clone(),
+            request_url: request_url.clone(),
+            request_query: request_query.clone(),
+            request_headers: headers_to_json(&headers).to_string(),
+            request_data: request_data.clone(),
+            response_status_code: 0,
+            response_headers: "[]".to_owned(),
+            response_data: None,
+            matched_rule_id: None,
+        };
+
+        if is_preflight(&method, &headers) {
+            let rule_cors = match get_requested_method(&headers) {
+                Some(requested_method) => MockingRule::find_cors(&request_url, &requested_method, &request_host).map_err(ApiError::from)?,
+                None => None,
+            };
+            if let Some(policy) = resolve_policy(&rule_cors) {
+                println!("[+] Answering CORS preflight...");
+                let resp = cors_preflight_response(&policy, &headers);
+                journal_entry.duration_ms = timer.elapsed().as_millis() as i64;
+                journal_entry.response_status_code = resp.status().as_u16() as i64;
+                journal_entry.response_headers = headers_to_json(resp.headers()).to_string();
+                Self::record_journal_entry(journal_entry);
+                return Ok(resp);
+            }
+        }
+
+        let graphql_operation = if graphql_rules.is_empty() {
+            None
+        } else {
+            parse_graphql_operation(&method, &request_query, buffered_body.as_deref())
+        };
+        if let Some(rule) = graphql_operation.and_then(|operation| find_matching_graphql_rule(graphql_rules, &operation)) {
+            println!("[+] GraphQL operation hit! Mocking response...");
+            let (mut resp, response_data) = mock_graphql_response(&rule);
+            apply_mock_cors_headers(&None, request_origin.as_deref(), resp.headers_mut());
+            journal_entry.duration_ms = timer.elapsed().as_millis() as i64;
+            journal_entry.response_status_code = resp.status().as_u16() as i64;
+            journal_entry.response_headers = headers_to_json(resp.headers()).to_string();
+            journal_entry.response_data = Some(response_data);
+            Self::record_journal_entry(journal_entry);
+            return Ok(resp);
+        }
+
+        if interceptable {
+            let matching_rule = MockingRule::find_matching(
+                &request_url,
+                &request_query,
+                &method,
+                &request_data,
+                &request_host
+            ).map_err(ApiError::from)?.into_iter().find(
+                |rule| form_matches(&rule.request_form, &rule.request_files, &request_form) && xml_matches(
+                    &rule.request_xpath, &rule.request_namespaces, &rule.request_soap_action, &headers, buffered_body.as_deref()
+                )
+            );
 
-        if settings::INTERCEPTABLE_METHODS.contains(&method){
-            let body = request_body.clone().trim().to_owned();
-
-            match MockingRule::find(
-                &Some(req_uri.to_string()),
-                &req_uri.query().map(|o|o.to_owned()),
-                &Some(method.clone()),
-                &(if body != "" { Some(body) } else { None })
-            ).map_err(
-                |error|
-                    HTTPResponse{
-                        status_code: StatusCode::UNPROCESSABLE_ENTITY,
-                        body: json_message(&error)
+            if let Some(rule) = matching_rule {
+                println!("[+] Endpoint hit! Mocking response...");
+                let (response_body, response_data) = match &rule.response_chunks {
+                    Some(chunks) => (
+                        stream_chunks(chunks, rule.response_sse),
+                        chunks_to_data(chunks, rule.response_sse)
+                    ),
+                    None => {
+                        let response_data = rule.response_data.unwrap_or_else(||"-".to_owned());
+                        (Body::from(response_data.clone()), response_data)
                     }
-            )?.pop() {
-                Some(rule) => {
-                    println!("[+] Endpoint hit! Mocking response...");
-                    let resp = Response::builder()
-                        .status(
-                            StatusCode::from_u16(
-                                rule.response_status_code as u16
-                            ).unwrap_or_else(
-                                /*
-                                    This happens if data are corrupted. Make sure
-                                    we can store only valid status codes into DB.
-                                */
-                                |e| panic!(e.to_string())
-                            )
+                };
+                let content_type = match &rule.response_content_type {
+                    Some(content_type) => content_type.as_str(),
+                    None if rule.response_sse => EVENT_STREAM_CONTENT_TYPE,
+                    None => "application/json; charset=UTF-8",
+                };
+                let mut resp = Response::builder()
+                    .status(
+                        StatusCode::from_u16(
+                            rule.response_status_code as u16
+                        ).unwrap_or_else(
+                            /*
+                                This happens if data are corrupted. Make sure
+                                we can store only valid status codes into DB.
+                            */
+                            |e| panic!("{}", e.to_string())
                         )
-                        .header("Access-Control-Allow-Origin", "*")
-                        .header("Access-Control-Allow-Headers", "*")
-                        .header("Access-Control-Allow-Methods", "GET, PUT, POST, DELETE, HEAD, OPTIONS")
-                        .header("Server", "Rockery - Rust Mocking Gateway")
-                        .header("X-Mocked", "1")
-                        .header("Content-Type", "application/json; charset=UTF-8")
-                        .body(
-                            Body::from(
-                                rule.response_data.unwrap_or_else(||"-".to_owned())
-                            )
-                        ).map_err(|_error|
-                            HTTPResponse{
-                                status_code: StatusCode::UNPROCESSABLE_ENTITY,
-                                body: json_message("[+] FATAL ERROR: Cannot generate mocked response")
-                            }
-                        )?;
-                    return Ok(resp);
-                },
-                None => () 
+                    )
+                    .header("Server", "Rockery - Rust Mocking Gateway")
+                    .header("X-Mocked", "1")
+                    .header("Content-Type", content_type)
+                    .body(response_body).map_err(
+                        |_error| ApiError::Internal("Cannot generate mocked response".to_owned())
+                    )?;
+
+                if rule.response_sse {
+                    resp.headers_mut().insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+                }
+                apply_mock_cors_headers(&rule.response_cors, request_origin.as_deref(), resp.headers_mut());
+
+                // Body was not needed for matching, read it for journal only.
+                if let Some(body) = streamed_body {
+                    journal_entry.request_data = read_body_bytes(body, *settings::MAX_BUFFERED_BODY).await.ok()
+                        .and_then(|body|body_to_data(&body));
+                }
+                journal_entry.duration_ms = timer.elapsed().as_millis() as i64;
+                journal_entry.response_status_code = resp.status().as_u16() as i64;
+                journal_entry.response_headers = headers_to_json(resp.headers()).to_string();
+                journal_entry.response_data = Some(response_data);
+                journal_entry.matched_rule_id = rule.id;
+                Self::record_journal_entry(journal_entry);
+                return Ok(resp);
             }
         }
+
+        if *settings::DEBUG {
+            Self::log_near_misses(&journal_entry);
+        }
         
-        let client = Client::new();
+        let target = resolve_target(&req_uri, &headers).map_err(ApiError::BadGateway)?;
 
-        let target_uri = Uri::builder()
-            .scheme(Scheme::HTTP)
-            .authority(&*settings::TARGET_SOCKET_ADDRESS.to_string())
-            .path_and_query(
-                req_uri.clone().into_parts().path_and_query.unwrap()
-            )
-            .build()
-            .unwrap();
+        let (proxy_body, request_capture) = match (buffered_body, streamed_body) {
+            (Some(body), _) => (Body::from(body), None),
+            (None, Some(body)) => {
+                // Trailers of requests are not forwarded.
+                let (body, _, capture) = capture_body(body, *settings::MAX_BUFFERED_BODY, None);
+                (body, Some(capture))
+            },
+            (None, None) => (Body::empty(), None),
+        };
         
-        let mut proxy_request = Request::new(Body::from(request_body.clone()));
-        *(proxy_request.uri_mut()) = target_uri;
-        *(proxy_request.version_mut()) = http_version;
-        *(proxy_request.method_mut()) = method;
-        *(proxy_request.headers_mut()) = headers;
+        let mut proxy_uri = target.uri;
+        let mut proxy_headers = headers;
+        let client_accepts_trailers = accepts_trailers(&proxy_headers);
+        strip_hop_by_hop_headers(&mut proxy_headers);
+        if client_accepts_trailers {
+            proxy_headers.insert(header::TE, HeaderValue::from_static("trailers"));
+        }
+        restore_host_header(&mut proxy_headers, &req_uri);
+        add_forwarding_headers(&mut proxy_headers, &req_uri, client.as_ref());
 
         if *settings::SPOOF_HOST_HEADER{
-            proxy_request.headers_mut().insert(
-                "Host", HeaderValue::from_static(&**settings::TARGET_HOST)
-            );
+            if let Ok(host) = HeaderValue::from_str(&target.host) {
+                proxy_headers.insert("Host", host);
+            }
+        }
+
+        let rewrite_rules = find_rewrite_rules(&method, &request_host).map_err(ApiError::from)?;
+        let rewrite_rules = rewrite_request(rewrite_rules, &mut proxy_uri, &mut proxy_headers).map_err(ApiError::BadGateway)?;
+
+        let patch_rules = find_patch_rules(&journal_entry.request_method, &request_host, req_uri.path()).map_err(ApiError::from)?;
+        if patches_body(&patch_rules) {
+            // Body can be patched only if target does not compress it.
+            proxy_headers.remove(header::ACCEPT_ENCODING);
         }
+
+        // Version of client request is not copied. Client picks HTTP/1.1 or HTTP/2 by connection with target.
+        let mut proxy_request = Request::new(proxy_body);
+        *(proxy_request.uri_mut()) = proxy_uri;
+        *(proxy_request.method_mut()) = method;
+        *(proxy_request.headers_mut()) = proxy_headers;
         
+        let (mut parts, body) = upstream::send(proxy_request).await.map_err(
+            |error| {
+                println!("[-] {}", error.describe());
+                error.as_http_response()
+            }
+        )?.into_parts();
+        // Response is framed for client connection, not for connection with target.
+        parts.version = http_version;
+        strip_hop_by_hop_headers(&mut parts.headers);
+        rewrite_response_headers(&rewrite_rules, &mut parts.headers);
+        if let Some(policy) = resolve_policy(&None) {
+            apply_cors_headers(&policy, request_origin.as_deref(), &mut parts.headers);
+        }
+
+        let body = if patch_rules.is_empty() {
+            body
+        } else {
+            println!("[+] Patching response of target...");
+            patch_response_head(&patch_rules, &mut parts);
+            if patches_body(&patch_rules) {
+                let response_body = read_body_bytes(body, *settings::MAX_BUFFERED_BODY).await.map_err(
+                    |error| ApiError::BadGateway(error.describe(*settings::MAX_BUFFERED_BODY))
+                )?;
+                Body::from(
+                    patch_response_body(&patch_rules, &mut parts.headers, &response_body).map_err(ApiError::BadGateway)?
+                )
+            } else {
+                body
+            }
+        };
+
+        journal_entry.response_status_code = parts.status.as_u16() as i64;
+        journal_entry.response_headers = headers_to_json(&parts.headers).to_string();
+
+        // Journal entry is recorded once both bodies are streamed through.
+        let (response_body, response_trailers, response_capture) = capture_body(
+            body, *settings::MAX_BUFFERED_BODY, *settings::UPSTREAM_READ_TIMEOUT
+        );
+        parts.extensions.insert(response_trailers);
+        tokio::spawn(async move {
+            if let Some(request_capture) = request_capture {
+                journal_entry.request_data = request_capture.await.ok().flatten()
+                    .and_then(|body|body_to_data(&body));
+            }
+            journal_entry.response_data = response_capture.await.ok().flatten()
+                .map(|body|String::from_utf8_lossy(&body).into_owned());
+            journal_entry.duration_ms = timer.elapsed().as_millis() as i64;
+            Self::record_journal_entry(journal_entry);
+        });
+
+        Ok(Response::from_parts(parts, response_body))
+    }
+}
+
+
+/// View for inspecting journal of received requests, which should
+/// be called statically only. Initializing function is `verify`,
+/// `unmatched` and `har`.
+pub struct JournalView {}
+impl JournalView {
+    fn get_json_non_negative_integer(
+        parsed_json: &JsonValue,
+        field_name: &str,
+        error_messages: &mut Vec<FieldError>
+    ) -> Option<i64>{
+        //! Extracts and validates optional non-negative integer field.
+        match parsed_json.get(field_name){
+            Some(field) => match field.as_i64() {
+                Some(value) if value >= 0 => Some(value),
+                _ => {
+                    error_messages.push(FieldError::new(field_name, format!("{} must be a non-negative integer", field_name)));
+                    None
+                }
+            },
+            None => None
+        }
+    }
+
+    fn get_json_expected_count(
+        parsed_json: &JsonValue,
+        error_messages: &mut Vec<FieldError>
+    ) -> Option<(i64, Option<i64>)>{
+        //! Extracts expected count range as `(min, max)`. Either exact `_rockery_expected_count`,
+        //! or `_rockery_expected_count_min` and/or `_rockery_expected_count_max` can be provided.
+        let exact = Self::get_json_non_negative_integer(parsed_json, "_rockery_expected_count", error_messages);
+        let min = Self::get_json_non_negative_integer(parsed_json, "_rockery_expected_count_min", error_messages);
+        let max = Self::get_json_non_negative_integer(parsed_json, "_rockery_expected_count_max", error_messages);
+
+        match (exact, min, max) {
+            (Some(_), Some(_), _) | (Some(_), _, Some(_)) => {
+                error_messages.push(FieldError::new(
+                    "_rockery_expected_count",
+                    "_rockery_expected_count cannot be combined with _rockery_expected_count_min or _rockery_expected_count_max".to_owned()
+                ));
+                None
+            },
+            (Some(count), None, None) => Some((count, Some(count))),
+            (None, Some(min), Some(max)) if min > max => {
+                error_messages.push(FieldError::new(
+                    "_rockery_expected_count_min",
+                    "_rockery_expected_count_min cannot be greater than _rockery_expected_count_max".to_owned()
+                ));
+                None
+            },
+            (None, None, None) => {
+                if parsed_json.get("_rockery_expected_count").is_none()
+                    && parsed_json.get("_rockery_expected_count_min").is_none()
+                    && parsed_json.get("_rockery_expected_count_max").is_none()
+                {
+                    error_messages.push(FieldError::new(
+                        "_rockery_expected_count",
+                        "Field _rockery_expected_count, _rockery_expected_count_min or _rockery_expected_count_max is required".to_owned()
+                    ));
+                }
+                None
+            },
+            (None, min, max) => Some((min.unwrap_or(0), max)),
+        }
+    }
+
+    fn find_journal_entries(
+        parsed_json: &JsonValue,
+        error_messages: &mut Vec<FieldError>
+    ) -> Option<Result<Vec<JournalEntry>, DbError>>{
+        //! Finds journal entries either by `_rockery_rule_id`, or by request matcher
+        //! in the same shape as request side of mocking rule.
+        if parsed_json.get("_rockery_rule_id").is_some(){
+            return Self::get_json_non_negative_integer(parsed_json, "_rockery_rule_id", error_messages).map(
+                JournalEntry::find_by_rule_id
+            );
+        }
+
+        let request_url : Option<String> = RuleView::get_json_request_url(parsed_json, error_messages);
+        let request_query : Option<String> = RuleView::get_json_request_query(parsed_json, error_messages);
+        let request_method : Option<Method> = RuleView::get_json_request_method(parsed_json, error_messages);
+        let request_data : Option<String> = RuleView::get_json_request_data(parsed_json, error_messages);
+        let request_host : Option<String> = RuleView::get_json_request_host(parsed_json, error_messages);
+
+        if !error_messages.is_empty(){
+            return None;
+        }
+        Some(JournalEntry::find(&request_url, &request_query, &request_method, &request_data, &request_host))
+    }
+
+    pub async fn verify(req: Request<Body>) -> Result<Response<Body>, HTTPResponse> {
+        //! Handles requests, which verify how many times a rule or request matcher
+        //! has been hit. Responds `200` if expectation is met, `417` otherwise.
+        RuleView::validate_rule_request(&req)?;
+        let parsed_body: JsonValue = parse_http_body_to_json(req).await.map_err(ApiError::MalformedBody)?;
+
+        let mut error_messages : Vec<FieldError> = vec![];
+        let expected_count = Self::get_json_expected_count(&parsed_body, &mut error_messages);
+        let found_entries = Self::find_journal_entries(&parsed_body, &mut error_messages);
+
+        let (expected_min, expected_max, found_entries) = match (expected_count, found_entries) {
+            (Some((min, max)), Some(found_entries)) if error_messages.is_empty() => (min, max, found_entries),
+            _ => return Err(ApiError::Validation(error_messages).into())
+        };
+
+        let found_entries = found_entries.map_err(ApiError::from)?;
+
+        let actual_count = found_entries.len() as i64;
+        let is_verified = actual_count >= expected_min && expected_max.is_none_or(|max| actual_count <= max);
+        let expectation = match expected_max {
+            Some(max) if max == expected_min => format!("exactly {}", max),
+            Some(max) => format!("between {} and {}", expected_min, max),
+            None => format!("at least {}", expected_min),
+        };
+
         Ok(
-            client.request(proxy_request).await.map_err(
-                |error|
-                    HTTPResponse{
-                        status_code: StatusCode::GATEWAY_TIMEOUT,
-                        body: json_message(&error.to_string())
-                    }
-            )?
+            (HTTPResponse{
+                status_code: if is_verified { StatusCode::OK } else { StatusCode::EXPECTATION_FAILED },
+                body: json!({
+                    "msg": if is_verified {
+                        format!("Verification passed. Expected {} and received {} matching requests", expectation, actual_count)
+                    } else {
+                        format!("Verification failed. Expected {} but received {} matching requests", expectation, actual_count)
+                    },
+                    "expected": {"min": expected_min, "max": expected_max},
+                    "actual_count": actual_count,
+                    "entries": found_entries.iter().map(|entry| entry.as_json()).collect::<Vec<JsonValue>>(),
+                }).to_string(),
+            }).as_hyper_response()
+        )
+    }
+
+    pub async fn unmatched(_req: Request<Body>) -> Result<Response<Body>, HTTPResponse> {
+        //! Handles requests, which list journal entries not matched by any rule,
+        //! together with closest rules and fields which differed. Available in debug mode only.
+        if !*settings::DEBUG {
+            return Err(
+                ApiError::NotFound("Near-miss diagnostics are available in debug mode only. Set ROCKERY_DEBUG=1".to_owned()).into()
+            );
+        }
+
+
+        let mut output : Vec<JsonValue> = vec![];
+        for entry in JournalEntry::find_unmatched().map_err(ApiError::from)? {
+            let near_misses = find_near_misses(
+                &entry.request_url,
+                &entry.request_query,
+                &entry.request_method,
+                &entry.request_data,
+                &entry.request_host
+            ).map_err(ApiError::from)?;
+
+            let mut entry_json = entry.as_json();
+            entry_json["near_misses"] = JsonValue::Array(
+                near_misses.iter().map(|near_miss| near_miss.as_json()).collect()
+            );
+            output.push(entry_json);
+        }
+
+        Ok(
+            (HTTPResponse{
+                status_code: StatusCode::OK,
+                body: JsonValue::Array(output).to_string(),
+            }).as_hyper_response()
+        )
+    }
+
+    pub async fn har(_req: Request<Body>) -> Result<Response<Body>, HTTPResponse> {
+        //! Handles requests, which export whole journal as HAR 1.2 document.
+        let entries = JournalEntry::all().map_err(ApiError::from)?;
+
+        Response::builder()
+            .status(StatusCode::OK)
+            .header("Content-Type", "application/json; charset=UTF-8")
+            .header("Content-Disposition", "attachment; filename=\"requests.har\"")
+            .body(Body::from(journal_to_har(&entries).to_string()))
+            .map_err(|error| ApiError::Internal(error.to_string()).into())
+    }
+}
+
+
+/// Handlers shared by views of rules stored as `Record`, which create,
+/// delete and list rules of single model. Should be called statically only.
+struct RecordView {}
+impl RecordView {
+    async fn create<T: Record>(
+        req: Request<Body>,
+        record_from_json: fn(&JsonValue) -> Result<T, Vec<FieldError>>,
+        json_key: &str,
+    ) -> Result<Response<Body>, HTTPResponse> {
+        //! Creates a new record from JSON body and returns it under `json_key`.
+        RuleView::validate_rule_request(&req)?;
+        let parsed_body: JsonValue = parse_http_body_to_json(req).await.map_err(ApiError::MalformedBody)?;
+
+        let mut new_record = record_from_json(&parsed_body).map_err(ApiError::Validation)?;
+
+        new_record.create().map_err(ApiError::from)?;
+
+        Ok(
+            (HTTPResponse{
+                status_code: StatusCode::CREATED,
+                body: json!({
+                    "msg": format!("{} has been created successfully!", new_record.describe()),
+                    json_key: new_record.as_json(),
+                }).to_string(),
+            }).as_hyper_response()
         )
     }
+
+    async fn delete<T: Record>(req: Request<Body>, id_field: &str) -> Result<Response<Body>, HTTPResponse> {
+        //! Deletes existing record by its id in `id_field` of JSON body.
+        RuleView::validate_rule_request(&req)?;
+        let parsed_body: JsonValue = parse_http_body_to_json(req).await.map_err(ApiError::MalformedBody)?;
+
+        let record_id = parsed_body.get(id_field).and_then(|id| id.as_i64()).ok_or_else(
+            || ApiError::Validation(vec![
+                FieldError::new(id_field, format!("Field {} is required and must be an integer", id_field))
+            ])
+        )?;
+
+        match T::find_by_id(record_id).map_err(ApiError::from)? {
+            Some(mut record) => {
+                record.delete().map_err(ApiError::from)?;
+                Ok(
+                    (HTTPResponse{
+                        status_code: StatusCode::OK,
+                        body: json_message(&format!("{} has been deleted successfully", record.describe()))
+                    }).as_hyper_response()
+                )
+            },
+            None => Err(
+                ApiError::NotFound(format!("{} does not exists and has not been deleted.", T::DISPLAY_NAME)).into()
+            )
+        }
+    }
+
+    async fn list<T: Record>() -> Result<Response<Body>, HTTPResponse> {
+        //! Lists all records in order they are evaluated.
+        let records = T::all().map_err(ApiError::from)?;
+
+        Ok(
+            (HTTPResponse{
+                status_code: StatusCode::OK,
+                body: JsonValue::Array(
+                    records.iter().map(|record| record.as_json()).collect()
+                ).to_string(),
+            }).as_hyper_response()
+        )
+    }
+}
+
+
+/// View for handling routing rules, which select target of proxied
+/// requests. Should be called statically only. Initializing function
+/// is `create`, `delete` and `list`.
+pub struct RouteView {}
+impl RouteView {
+    pub async fn create(req: Request<Body>) -> Result<Response<Body>, HTTPResponse> {
+        //! Handles requests, which attempt to create a new routing rule.
+        RecordView::create(req, routing_rule_from_json, "route").await
+    }
+
+    pub async fn delete(req: Request<Body>) -> Result<Response<Body>, HTTPResponse> {
+        //! Handles requests, which attempt to delete existing routing rule by `_rockery_route_id`.
+        RecordView::delete::<RoutingRule>(req, "_rockery_route_id").await
+    }
+
+    pub async fn list(_req: Request<Body>) -> Result<Response<Body>, HTTPResponse> {
+        //! Handles requests, which list routing rules in order they are evaluated.
+        RecordView::list::<RoutingRule>().await
+    }
+}
+
+
+/// View for handling rewrite rules, which modify headers and path of
+/// proxied requests. Should be called statically only. Initializing
+/// function is `create`, `delete` and `list`.
+pub struct RewriteView {}
+impl RewriteView {
+    pub async fn create(req: Request<Body>) -> Result<Response<Body>, HTTPResponse> {
+        //! Handles requests, which attempt to create a new rewrite rule.
+        RecordView::create(req, rewrite_rule_from_json, "rewrite").await
+    }
+
+    pub async fn delete(req: Request<Body>) -> Result<Response<Body>, HTTPResponse> {
+        //! Handles requests, which attempt to delete existing rewrite rule by `_rockery_rewrite_id`.
+        RecordView::delete::<RewriteRule>(req, "_rockery_rewrite_id").await
+    }
+
+    pub async fn list(_req: Request<Body>) -> Result<Response<Body>, HTTPResponse> {
+        //! Handles requests, which list rewrite rules in order they are applied.
+        RecordView::list::<RewriteRule>().await
+    }
+}
+
+
+/// View for handling patch rules, which modify responses of target.
+/// Should be called statically only. Initializing function is `create`,
+/// `delete` and `list`.
+pub struct PatchView {}
+impl PatchView {
+    pub async fn create(req: Request<Body>) -> Result<Response<Body>, HTTPResponse> {
+        //! Handles requests, which attempt to create a new patch rule.
+        RecordView::create(req, patch_rule_from_json, "patch").await
+    }
+
+    pub async fn delete(req: Request<Body>) -> Result<Response<Body>, HTTPResponse> {
+        //! Handles requests, which attempt to delete existing patch rule by `_rockery_patch_id`.
+        RecordView::delete::<PatchRule>(req, "_rockery_patch_id").await
+    }
+
+    pub async fn list(_req: Request<Body>) -> Result<Response<Body>, HTTPResponse> {
+        //! Handles requests, which list patch rules in order they are applied.
+        RecordView::list::<PatchRule>().await
+    }
+}
+
+
+/// View for handling gRPC rules, which mock methods of gRPC services.
+/// Should be called statically only. Initializing function is `create`,
+/// `delete` and `list`.
+pub struct GrpcRuleView {}
+impl GrpcRuleView {
+    pub async fn create(req: Request<Body>) -> Result<Response<Body>, HTTPResponse> {
+        //! Handles requests, which attempt to create a new gRPC rule.
+        RecordView::create(req, grpc_rule_from_json, "grpc_rule").await
+    }
+
+    pub async fn delete(req: Request<Body>) -> Result<Response<Body>, HTTPResponse> {
+        //! Handles requests, which attempt to delete existing gRPC rule by `_rockery_grpc_rule_id`.
+        RecordView::delete::<GrpcRule>(req, "_rockery_grpc_rule_id").await
+    }
+
+    pub async fn list(_req: Request<Body>) -> Result<Response<Body>, HTTPResponse> {
+        //! Handles requests, which list gRPC rules.
+        RecordView::list::<GrpcRule>().await
+    }
+}
+
+
+/// View for handling GraphQL rules, which mock operations of GraphQL endpoints.
+/// Should be called statically only. Initializing function is `create`,
+/// `delete` and `list`.
+pub struct GraphqlRuleView {}
+impl GraphqlRuleView {
+    pub async fn create(req: Request<Body>) -> Result<Response<Body>, HTTPResponse> {
+        //! Handles requests, which attempt to create a new GraphQL rule.
+        RecordView::create(req, graphql_rule_from_json, "graphql_rule").await
+    }
+
+    pub async fn delete(req: Request<Body>) -> Result<Response<Body>, HTTPResponse> {
+        //! Handles requests, which attempt to delete existing GraphQL rule by `_rockery_graphql_rule_id`.
+        RecordView::delete::<GraphqlRule>(req, "_rockery_graphql_rule_id").await
+    }
+
+    pub async fn list(_req: Request<Body>) -> Result<Response<Body>, HTTPResponse> {
+        //! Handles requests, which list GraphQL rules.
+        RecordView::list::<GraphqlRule>().await
+    }
+}
+
+
+/// View for gRPC calls, which should be called statically only.
+/// Initializing function is `default`.
+pub struct GrpcView {}
+impl GrpcView {
+    pub async fn default(req: Request<Body>) -> Result<Response<Body>, HTTPResponse> {
+        //! Mocks gRPC call by matching gRPC rule. Calls not matched by any
+        //! rule are handled by `RuleView::default`, e.g. proxied to target.
+        let grpc_rule = match parse_grpc_path(req.uri().path()) {
+            Some((service, method)) => GrpcRule::find_matching(service, method).map_err(ApiError::from)?,
+            None => None,
+        };
+        let grpc_rule = match grpc_rule {
+            Some(grpc_rule) => grpc_rule,
+            None => return RuleView::default(req).await,
+        };
+
+        println!("[+] gRPC method hit! Mocking response...");
+        let started_at = unix_timestamp_millis();
+        let timer = Instant::now();
+        let (parts, body) = req.into_parts();
+
+        let request_body = read_body_bytes(body, *settings::MAX_BUFFERED_BODY).await.map_err(ApiError::from)?;
+        let (response, response_data) = mock_grpc_response(&grpc_rule);
+
+        Self::record(&parts, decode_grpc_request_data(&grpc_rule, &request_body), &response, response_data, started_at, timer);
+        Ok(response)
+    }
+
+    fn record(
+        parts: &RequestParts,
+        request_data: Option<String>,
+        response: &Response<Body>,
+        response_data: String,
+        started_at: i64,
+        timer: Instant
+    ) {
+        //! Records mocked call into journal. Messages are stored as JSON.
+        RuleView::record_journal_entry(
+            JournalEntry {
+                id: None,
+                started_at,
+                duration_ms: timer.elapsed().as_millis() as i64,
+                request_method: parts.method.clone(),
+                request_host: get_request_host(&parts.uri, &parts.headers),
+                request_url: parts.uri.path().to_owned(),
+                request_query: None,
+                request_headers: headers_to_json(&parts.headers).to_string(),
+                request_data,
+                response_status_code: response.status().as_u16() as i64,
+                response_headers: headers_to_json(response.headers()).to_string(),
+                response_data: Some(response_data),
+                matched_rule_id: None,
+            }
+        );
+    }
+}
+
+
+/// View for handling WebSocket rules, which mock WebSocket endpoints.
+/// Should be called statically only. Initializing function is `create`,
+/// `delete` and `list`.
+pub struct WebSocketRuleView {}
+impl WebSocketRuleView {
+    pub async fn create(req: Request<Body>) -> Result<Response<Body>, HTTPResponse> {
+        //! Handles requests, which attempt to create a new WebSocket rule.
+        RecordView::create(req, websocket_rule_from_json, "ws_rule").await
+    }
+
+    pub async fn delete(req: Request<Body>) -> Result<Response<Body>, HTTPResponse> {
+        //! Handles requests, which attempt to delete existing WebSocket rule by `_rockery_ws_rule_id`.
+        RecordView::delete::<WebSocketRule>(req, "_rockery_ws_rule_id").await
+    }
+
+    pub async fn list(_req: Request<Body>) -> Result<Response<Body>, HTTPResponse> {
+        //! Handles requests, which list WebSocket rules in order they are matched.
+        RecordView::list::<WebSocketRule>().await
+    }
+}
+
+
+/// View for forward proxy specific requests, which should be
+/// called statically only. Initializing function is `connect`.
+pub struct ProxyView {}
+impl ProxyView {
+    pub async fn connect(req: Request<Body>) -> Result<Response<Body>, HTTPResponse> {
+        //! Handles CONNECT requests of clients using Rockery as forward proxy. Tunnel is
+        //! either passed through to target host, or intercepted if `ROCKERY_MITM` is enabled.
+        if !*settings::FORWARD_PROXY {
+            return Err(ApiError::Forbidden("CONNECT requires forward proxy, set ROCKERY_FORWARD_PROXY=1 to enable it".to_owned()).into());
+        }
+        let authority = req.uri().authority().cloned().ok_or_else(
+            || ApiError::BadRequest("CONNECT request must target host:port".to_owned())
+        )?;
+        let client = *req.extensions().get::<ClientConnection>().ok_or_else(
+            || ApiError::Internal("Client connection is unknown".to_owned())
+        )?;
+
+        tokio::spawn(async move {
+            let upgraded = match req.into_body().on_upgrade().await {
+                Ok(upgraded) => upgraded,
+                Err(error) => return println!("[-] CONNECT tunnel to {} failed to upgrade: {}", authority, error),
+            };
+
+            let result = if *settings::ROCKERY_MITM {
+                intercept(upgraded, authority.clone(), client).await
+            } else {
+                tunnel(upgraded, &authority).await
+            };
+            if let Err(error) = result {
+                println!("[-] CONNECT tunnel to {} failed: {}", authority, error);
+            }
+        });
+
+        Ok(Response::new(Body::empty()))
+    }
+}
+
+
+/// View for WebSocket connections, which should be called
+/// statically only. Initializing function is `proxy`.
+pub struct WebSocketView {}
+impl WebSocketView {
+    pub async fn proxy(req: Request<Body>) -> Result<Response<Body>, HTTPResponse> {
+        //! Forwards WebSocket handshake to target. If target accepts it, both
+        //! upgraded connections are spliced together and messages are relayed.
+        let started_at = unix_timestamp_millis();
+        let timer = Instant::now();
+
+        let (parts, client_body) = req.into_parts();
+        let client = parts.extensions.get::<ClientConnection>().copied();
+        let request_host = get_request_host(&parts.uri, &parts.headers);
+
+        let websocket_rule = WebSocketRule::find_matching(parts.uri.path(), &request_host).map_err(ApiError::from)?;
+        if let Some(websocket_rule) = websocket_rule {
+            return Self::mock(parts, client_body, websocket_rule, started_at, timer);
+        }
+
+        let target = resolve_target(&parts.uri, &parts.headers).map_err(ApiError::BadGateway)?;
+
+        let mut proxy_uri = target.uri;
+        let mut proxy_headers = parts.headers.clone();
+        strip_hop_by_hop_headers(&mut proxy_headers);
+        // Relayed frames are never compressed, so no extension can be negotiated.
+        proxy_headers.remove(header::SEC_WEBSOCKET_EXTENSIONS);
+        proxy_headers.insert(header::CONNECTION, HeaderValue::from_static("upgrade"));
+        proxy_headers.insert(header::UPGRADE, HeaderValue::from_static("websocket"));
+        add_forwarding_headers(&mut proxy_headers, &parts.uri, client.as_ref());
+
+        if *settings::SPOOF_HOST_HEADER{
+            if let Ok(host) = HeaderValue::from_str(&target.host) {
+                proxy_headers.insert("Host", host);
+            }
+        }
+
+        let rewrite_rules = find_rewrite_rules(&parts.method, &request_host).map_err(ApiError::from)?;
+        let rewrite_rules = rewrite_request(rewrite_rules, &mut proxy_uri, &mut proxy_headers).map_err(ApiError::BadGateway)?;
+
+        let mut proxy_request = Request::new(Body::empty());
+        *(proxy_request.uri_mut()) = proxy_uri;
+        *(proxy_request.method_mut()) = parts.method.clone();
+        *(proxy_request.headers_mut()) = proxy_headers;
+
+        let (mut response_parts, target_body) = upstream::send(proxy_request).await.map_err(
+            |error| {
+                println!("[-] {}", error.describe());
+                error.as_http_response()
+            }
+        )?.into_parts();
+        response_parts.version = parts.version;
+        strip_hop_by_hop_headers(&mut response_parts.headers);
+        rewrite_response_headers(&rewrite_rules, &mut response_parts.headers);
+
+        let accepted = response_parts.status == StatusCode::SWITCHING_PROTOCOLS;
+        if accepted {
+            response_parts.headers.insert(header::CONNECTION, HeaderValue::from_static("upgrade"));
+            response_parts.headers.insert(header::UPGRADE, HeaderValue::from_static("websocket"));
+        }
+
+        let journal_entry_id = RuleView::record_journal_entry(
+            JournalEntry {
+                id: None,
+                started_at,
+                duration_ms: timer.elapsed().as_millis() as i64,
+                request_method: parts.method.clone(),
+                request_host,
+                request_url: parts.uri.path_and_query().map_or("/".to_owned(), |path_and_query| path_and_query.to_string()),
+                request_query: parts.uri.query().map(|o|o.to_owned()),
+                request_headers: headers_to_json(&parts.headers).to_string(),
+                request_data: None,
+                response_status_code: response_parts.status.as_u16() as i64,
+                response_headers: headers_to_json(&response_parts.headers).to_string(),
+                response_data: None,
+                matched_rule_id: None,
+            }
+        );
+
+        if !accepted {
+            // Target refused the upgrade, its response is returned untouched.
+            return Ok(Response::from_parts(response_parts, target_body));
+        }
+
+        let journal_entry_id = journal_entry_id.filter(|_| *settings::WEBSOCKET_LOG);
+        tokio::spawn(async move {
+            match try_join(client_body.on_upgrade(), target_body.on_upgrade()).await {
+                Ok((client_upgraded, target_upgraded)) => splice(client_upgraded, target_upgraded, journal_entry_id).await,
+                Err(error) => println!("[-] WebSocket connection failed to upgrade: {}", error),
+            }
+        });
+
+        Ok(Response::from_parts(response_parts, Body::empty()))
+    }
+
+    fn mock(
+        parts: RequestParts,
+        client_body: Body,
+        websocket_rule: WebSocketRule,
+        started_at: i64,
+        timer: Instant
+    ) -> Result<Response<Body>, HTTPResponse> {
+        //! Accepts WebSocket handshake on behalf of target and serves the connection
+        //! by conversation scripted in `websocket_rule`.
+        let mut handshake_request = Request::new(());
+        *(handshake_request.method_mut()) = parts.method.clone();
+        *(handshake_request.version_mut()) = parts.version;
+        *(handshake_request.uri_mut()) = parts.uri.clone();
+        *(handshake_request.headers_mut()) = parts.headers.clone();
+
+        let (mut response_parts, _) = create_response(&handshake_request).map_err(
+            |error| ApiError::BadRequest(format!("Invalid WebSocket handshake: {}", error))
+        )?.into_parts();
+        response_parts.headers.insert("X-Mocked", HeaderValue::from_static("1"));
+
+        let journal_entry_id = RuleView::record_journal_entry(
+            JournalEntry {
+                id: None,
+                started_at,
+                duration_ms: timer.elapsed().as_millis() as i64,
+                request_method: parts.method.clone(),
+                request_host: get_request_host(&parts.uri, &parts.headers),
+                request_url: parts.uri.path_and_query().map_or("/".to_owned(), |path_and_query| path_and_query.to_string()),
+                request_query: parts.uri.query().map(|o|o.to_owned()),
+                request_headers: headers_to_json(&parts.headers).to_string(),
+                request_data: None,
+                response_status_code: response_parts.status.as_u16() as i64,
+                response_headers: headers_to_json(&response_parts.headers).to_string(),
+                response_data: None,
+                matched_rule_id: None,
+            }
+        ).filter(|_| *settings::WEBSOCKET_LOG);
+
+        tokio::spawn(async move {
+            match client_body.on_upgrade().await {
+                Ok(client_upgraded) => mock_conversation(client_upgraded, websocket_rule, journal_entry_id).await,
+                Err(error) => println!("[-] WebSocket connection failed to upgrade: {}", error),
+            }
+        });
+
+        Ok(Response::from_parts(response_parts, Body::empty()))
+    }
 }
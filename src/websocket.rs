@@ -0,0 +1,329 @@
+use std::time::Duration;
+
+use futures::{future, Sink, SinkExt, Stream, StreamExt};
+use hyper::HeaderMap;
+use hyper::header::{self, HeaderName};
+use hyper::upgrade::Upgraded;
+use regex::Regex;
+use serde_json::Value as JsonValue;
+use tokio::time::{self, Instant};
+use tokio_tungstenite::WebSocketStream;
+use tokio_tungstenite::tungstenite::{Error as WebSocketError, Message};
+use tokio_tungstenite::tungstenite::protocol::{CloseFrame, Role};
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+
+use crate::db::{Record, WebSocketFrame, WebSocketRule};
+use crate::errors::FieldError;
+use crate::utils::{get_json_optional_string, unix_timestamp_millis};
+
+/// Time given to the other side to answer close frame.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+fn header_contains_token(headers: &HeaderMap, name: HeaderName, token: &str) -> bool {
+    //! Checks if comma separated header contains `token`, ignoring case.
+    headers.get_all(name).iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|value| value.trim().eq_ignore_ascii_case(token))
+}
+
+pub fn is_websocket_upgrade(headers: &HeaderMap) -> bool {
+    //! Checks if request asks for upgrade to WebSocket.
+    header_contains_token(headers, header::CONNECTION, "upgrade")
+        && header_contains_token(headers, header::UPGRADE, "websocket")
+}
+
+fn record_frame(message: &Message, direction: &str, journal_entry_id: i64) {
+    //! Saves relayed data or close frame into journal.
+    let (opcode, data) = match message {
+        Message::Text(text) => (1, text.to_owned()),
+        Message::Binary(data) => (2, base64::encode(data)),
+        Message::Close(close_frame) => (
+            8, close_frame.as_ref().map_or(String::new(), |close_frame| format!("{} {}", close_frame.code, close_frame.reason))
+        ),
+        _ => return,
+    };
+    let mut frame = WebSocketFrame {
+        id: None,
+        journal_entry_id,
+        sent_at: unix_timestamp_millis(),
+        direction: direction.to_owned(),
+        opcode,
+        data,
+    };
+    if let Err(error) = frame.create() {
+        println!("[-] Failed to record WebSocket frame into journal: {}", error);
+    }
+}
+
+async fn relay<S, K>(mut stream: S, mut sink: K, direction: &str, journal_entry_id: Option<i64>) -> Result<(), WebSocketError>
+where
+    S: Stream<Item = Result<Message, WebSocketError>> + Unpin,
+    K: Sink<Message, Error = WebSocketError> + Unpin,
+{
+    //! Relays messages from `stream` to `sink` until either side closes connection.
+    //! Pings and pongs are answered on each connection separately, so they are not relayed.
+    while let Some(message) = stream.next().await {
+        let message = message?;
+        if message.is_ping() || message.is_pong() {
+            continue;
+        }
+        if let Some(journal_entry_id) = journal_entry_id {
+            record_frame(&message, direction, journal_entry_id);
+        }
+        let is_close = message.is_close();
+        sink.send(message).await?;
+        if is_close {
+            break;
+        }
+    }
+    Ok(())
+}
+
+pub async fn splice(client: Upgraded, target: Upgraded, journal_entry_id: Option<i64>) {
+    //! Relays WebSocket messages between upgraded connections of client and target in
+    //! both directions. Frames are recorded to journal entry, if `journal_entry_id` is set.
+    let client = WebSocketStream::from_raw_socket(client, Role::Server, None).await;
+    let target = WebSocketStream::from_raw_socket(target, Role::Client, None).await;
+    let (client_sink, client_stream) = client.split();
+    let (target_sink, target_stream) = target.split();
+
+    let (result, other_direction) = future::select(
+        Box::pin(relay(client_stream, target_sink, "send", journal_entry_id)),
+        Box::pin(relay(target_stream, client_sink, "receive", journal_entry_id)),
+    ).await.factor_first();
+    // Let close frame be answered by the other side, unless it is gone.
+    let _ = tokio::time::timeout(CLOSE_TIMEOUT, other_direction).await;
+
+    match result {
+        Ok(()) | Err(WebSocketError::ConnectionClosed) | Err(WebSocketError::AlreadyClosed) => (),
+        Err(error) => println!("[-] WebSocket connection failed: {}", error),
+    }
+}
+
+fn get_json_optional_millis(parsed_json: &JsonValue, field_name: &str, error_messages: &mut Vec<FieldError>) -> Option<i64>{
+    //! Extracts optional positive number of milliseconds.
+    match parsed_json.get(field_name) {
+        Some(JsonValue::Null) | None => None,
+        Some(value) => match value.as_i64() {
+            Some(millis) if millis > 0 => Some(millis),
+            _ => {
+                error_messages.push(FieldError::new(field_name, format!("{} must be a positive number of milliseconds", field_name)));
+                None
+            }
+        }
+    }
+}
+
+pub fn websocket_rule_from_json(parsed_json: &JsonValue) -> Result<WebSocketRule, Vec<FieldError>>{
+    //! Creates `WebSocketRule` from provided `serde_json::Value` or returns error messages if validation fails.
+    //! Rule is not saved to db!
+    let mut error_messages : Vec<FieldError> = vec![];
+
+    let request_path = match get_json_optional_string(parsed_json, "_rockery_ws_path", &mut error_messages) {
+        Some(path) if path.starts_with('/') => path,
+        _ => {
+            error_messages.push(FieldError::new("_rockery_ws_path", "_rockery_ws_path must be a string starting with /".to_owned()));
+            String::new()
+        }
+    };
+    let request_host = get_json_optional_string(parsed_json, "_rockery_ws_host", &mut error_messages);
+
+    let on_connect = match parsed_json.get("_rockery_ws_on_connect") {
+        Some(JsonValue::Null) | None => "[]".to_owned(),
+        Some(JsonValue::Array(messages)) => JsonValue::Array(messages.to_owned()).to_string(),
+        Some(_) => {
+            error_messages.push(FieldError::new("_rockery_ws_on_connect", "_rockery_ws_on_connect must be an array of messages".to_owned()));
+            "[]".to_owned()
+        }
+    };
+
+    let replies = match parsed_json.get("_rockery_ws_replies") {
+        Some(JsonValue::Null) | None => "[]".to_owned(),
+        Some(JsonValue::Array(replies)) => {
+            for reply in replies.iter() {
+                match (reply.get("pattern").and_then(|pattern| pattern.as_str()), reply.get("message")) {
+                    (Some(pattern), Some(_)) => if let Err(error) = Regex::new(pattern) {
+                        error_messages.push(FieldError::new("_rockery_ws_replies", format!("_rockery_ws_replies contains invalid regex {}: {}", pattern, error)));
+                    },
+                    _ => error_messages.push(FieldError::new(
+                        "_rockery_ws_replies",
+                        "_rockery_ws_replies must contain objects with string pattern and message".to_owned()
+                    )),
+                }
+            }
+            JsonValue::Array(replies.to_owned()).to_string()
+        },
+        Some(_) => {
+            error_messages.push(FieldError::new("_rockery_ws_replies", "_rockery_ws_replies must be an array of replies".to_owned()));
+            "[]".to_owned()
+        }
+    };
+
+    let periodic_message = match parsed_json.get("_rockery_ws_periodic_message") {
+        Some(JsonValue::Null) | None => None,
+        Some(message) => Some(message.to_string()),
+    };
+    let periodic_interval_ms = get_json_optional_millis(parsed_json, "_rockery_ws_periodic_interval_ms", &mut error_messages);
+    if periodic_message.is_some() != periodic_interval_ms.is_some() {
+        error_messages.push(FieldError::new(
+            "_rockery_ws_periodic_message",
+            "_rockery_ws_periodic_message and _rockery_ws_periodic_interval_ms must be set together".to_owned()
+        ));
+    }
+
+    let close_after_ms = get_json_optional_millis(parsed_json, "_rockery_ws_close_after_ms", &mut error_messages);
+    let close_code = match parsed_json.get("_rockery_ws_close_code") {
+        Some(JsonValue::Null) | None => 1000,
+        Some(close_code) => match close_code.as_i64() {
+            Some(close_code) if (1000..=4999).contains(&close_code) => close_code,
+            _ => {
+                error_messages.push(FieldError::new("_rockery_ws_close_code", "_rockery_ws_close_code must be a number between 1000 and 4999".to_owned()));
+                1000
+            }
+        }
+    };
+    let close_reason = get_json_optional_string(parsed_json, "_rockery_ws_close_reason", &mut error_messages);
+
+    if !error_messages.is_empty() {
+        return Err(error_messages);
+    }
+    Ok(
+        WebSocketRule {
+            id: None,
+            request_path,
+            request_host: request_host.map(|host| host.to_lowercase()),
+            on_connect,
+            replies,
+            periodic_message,
+            periodic_interval_ms,
+            close_after_ms,
+            close_code,
+            close_reason,
+        }
+    )
+}
+
+fn message_from_json(message: &JsonValue) -> Message {
+    //! Strings are sent as they are, other JSON values are sent serialized.
+    match message {
+        JsonValue::String(text) => Message::Text(text.to_owned()),
+        message => Message::Text(message.to_string()),
+    }
+}
+
+fn find_reply(replies: &[(Regex, JsonValue)], text: &str) -> Option<Message> {
+    //! Returns reply of first pattern matching `text`. Capture groups can be referenced
+    //! from text replies as `$1` or `${name}`.
+    replies.iter().find_map(|(pattern, message)| {
+        let captures = pattern.captures(text)?;
+        Some(match message {
+            JsonValue::String(template) => {
+                let mut text = String::new();
+                captures.expand(template, &mut text);
+                Message::Text(text)
+            },
+            message => message_from_json(message),
+        })
+    })
+}
+
+async fn send_scripted<K>(sink: &mut K, message: Message, journal_entry_id: Option<i64>) -> Result<(), WebSocketError>
+where
+    K: Sink<Message, Error = WebSocketError> + Unpin,
+{
+    //! Sends message of mocked endpoint to client.
+    if let Some(journal_entry_id) = journal_entry_id {
+        record_frame(&message, "receive", journal_entry_id);
+    }
+    sink.send(message).await
+}
+
+async fn converse(client: Upgraded, websocket_rule: WebSocketRule, journal_entry_id: Option<i64>) -> Result<(), WebSocketError> {
+    //! Runs conversation scripted by `websocket_rule` until client or the script closes connection.
+    let (mut sink, mut stream) = WebSocketStream::from_raw_socket(client, Role::Server, None).await.split();
+
+    let on_connect : Vec<JsonValue> = serde_json::from_str(&websocket_rule.on_connect).unwrap_or_default();
+    let replies : Vec<JsonValue> = serde_json::from_str(&websocket_rule.replies).unwrap_or_default();
+    let replies : Vec<(Regex, JsonValue)> = replies.iter().filter_map(|reply| Some((
+        Regex::new(reply.get("pattern")?.as_str()?).ok()?,
+        reply.get("message")?.to_owned(),
+    ))).collect();
+    let periodic_message = websocket_rule.periodic_message.as_ref().and_then(
+        |message| serde_json::from_str::<JsonValue>(message).ok()
+    );
+    let mut periodic_interval = websocket_rule.periodic_interval_ms.map(|millis| {
+        let period = Duration::from_millis(millis as u64);
+        time::interval_at(Instant::now() + period, period)
+    });
+    let close_at = websocket_rule.close_after_ms.map(|millis| Instant::now() + Duration::from_millis(millis as u64));
+
+    for message in on_connect.iter() {
+        send_scripted(&mut sink, message_from_json(message), journal_entry_id).await?;
+    }
+
+    loop {
+        let periodic_tick = async {
+            match periodic_interval.as_mut() {
+                Some(periodic_interval) => periodic_interval.tick().await,
+                None => future::pending().await,
+            }
+        };
+        let close_deadline = async {
+            match close_at {
+                Some(close_at) => time::delay_until(close_at).await,
+                None => future::pending().await,
+            }
+        };
+
+        tokio::select! {
+            message = stream.next() => {
+                let message = match message {
+                    Some(message) => message?,
+                    None => break,
+                };
+                if let Some(journal_entry_id) = journal_entry_id {
+                    record_frame(&message, "send", journal_entry_id);
+                }
+                match message {
+                    Message::Text(text) => if let Some(reply) = find_reply(&replies, &text) {
+                        send_scripted(&mut sink, reply, journal_entry_id).await?;
+                    },
+                    // Close reply is queued by tungstenite and flushed when sink is closed.
+                    Message::Close(_) => break,
+                    _ => (),
+                }
+            },
+            _ = periodic_tick => {
+                if let Some(message) = &periodic_message {
+                    send_scripted(&mut sink, message_from_json(message), journal_entry_id).await?;
+                }
+            },
+            _ = close_deadline => {
+                let close_frame = CloseFrame {
+                    code: CloseCode::from(websocket_rule.close_code as u16),
+                    reason: websocket_rule.close_reason.clone().unwrap_or_default().into(),
+                };
+                send_scripted(&mut sink, Message::Close(Some(close_frame)), journal_entry_id).await?;
+                // Give client a chance to answer close frame.
+                let _ = time::timeout(CLOSE_TIMEOUT, async {
+                    while let Some(Ok(message)) = stream.next().await {
+                        if message.is_close() {
+                            break;
+                        }
+                    }
+                }).await;
+                break;
+            },
+        }
+    }
+    sink.close().await
+}
+
+pub async fn mock_conversation(client: Upgraded, websocket_rule: WebSocketRule, journal_entry_id: Option<i64>) {
+    //! Serves upgraded client connection by mocked WebSocket endpoint.
+    match converse(client, websocket_rule, journal_entry_id).await {
+        Ok(()) | Err(WebSocketError::ConnectionClosed) | Err(WebSocketError::AlreadyClosed) => (),
+        Err(error) => println!("[-] Mocked WebSocket connection failed: {}", error),
+    }
+}
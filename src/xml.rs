@@ -0,0 +1,273 @@
+use hyper::HeaderMap;
+use hyper::header;
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde_json::{Map as JsonMap, Value as JsonValue};
+use sxd_document::parser as xml_parser;
+use sxd_xpath::{Context, Factory, XPath};
+
+use crate::errors::FieldError;
+use crate::forms::header_parameters;
+
+/// Content type of mocked XML responses, unless rule specifies other.
+pub const XML_CONTENT_TYPE: &str = "text/xml; charset=UTF-8";
+
+lazy_static! {
+    /// String literals of XPath expression, which cannot contain prefixed names.
+    static ref STRING_LITERAL: Regex = Regex::new(r#"'[^']*'|"[^"]*""#).unwrap();
+    /// Prefixed names, e.g. `m:GetQuote`. Axes like `child::` are followed by second colon, variables by `$`.
+    static ref PREFIXED_NAME: Regex = Regex::new(r"(?:^|[^\w.:$-])([A-Za-z_][\w.-]*):[A-Za-z_*]").unwrap();
+}
+
+fn compile_xpath(expression: &str) -> Result<XPath, String> {
+    //! Compiles XPath expression.
+    match Factory::new().build(expression) {
+        Ok(Some(xpath)) => Ok(xpath),
+        Ok(None) => Err("XPath expression is empty".to_owned()),
+        Err(error) => Err(error.to_string()),
+    }
+}
+
+pub fn get_json_request_xpath(
+    parsed_json: &JsonValue,
+    field_name: &str,
+    error_messages: &mut Vec<FieldError>
+) -> Option<String>{
+    //! Extracts and validates array of XPath expressions, each of them must be true for
+    //! body to match, e.g. `/soap:Envelope/soap:Body/m:GetQuote/m:Symbol = 'ACME'`.
+    let expressions = match parsed_json.get(field_name) {
+        Some(JsonValue::Null) | None => return None,
+        Some(JsonValue::Array(expressions)) if !expressions.is_empty() => expressions,
+        Some(_) => {
+            error_messages.push(FieldError::new(field_name, format!("{} must be a non-empty array of XPath expressions", field_name)));
+            return None;
+        }
+    };
+    for expression in expressions.iter() {
+        match expression.as_str().map(compile_xpath) {
+            Some(Ok(_)) => (),
+            Some(Err(error)) => error_messages.push(FieldError::new(field_name, format!("{} contains invalid XPath {}: {}", field_name, expression, error))),
+            None => error_messages.push(FieldError::new(field_name, format!("{} must contain XPath expressions as strings", field_name))),
+        }
+    }
+    Some(JsonValue::Array(expressions.to_owned()).to_string())
+}
+
+pub fn get_json_request_namespaces(
+    parsed_json: &JsonValue,
+    field_name: &str,
+    error_messages: &mut Vec<FieldError>
+) -> Option<String>{
+    //! Extracts and validates object mapping prefixes used in XPath expressions to namespace URIs.
+    match parsed_json.get(field_name) {
+        Some(JsonValue::Null) | None => None,
+        Some(JsonValue::Object(namespaces)) if namespaces.values().all(|uri| uri.is_string()) => {
+            Some(JsonValue::Object(namespaces.to_owned()).to_string())
+        },
+        Some(_) => {
+            error_messages.push(FieldError::new(field_name, format!("{} must be an object of prefixes and namespace URIs", field_name)));
+            None
+        }
+    }
+}
+
+pub fn get_json_response_xml(
+    parsed_json: &JsonValue,
+    field_name: &str,
+    error_messages: &mut Vec<FieldError>
+) -> Option<String>{
+    //! Extracts XML response body and checks it is well-formed.
+    match parsed_json.get(field_name) {
+        Some(JsonValue::Null) | None => None,
+        Some(JsonValue::String(xml)) => {
+            if let Err(error) = xml_parser::parse(xml) {
+                error_messages.push(FieldError::new(field_name, format!("{} is not well-formed XML: {}", field_name, error)));
+            }
+            Some(xml.to_owned())
+        },
+        Some(_) => {
+            error_messages.push(FieldError::new(field_name, format!("{} must be a string", field_name)));
+            None
+        }
+    }
+}
+
+fn parse_namespaces(request_namespaces: &Option<String>) -> JsonMap<String, JsonValue> {
+    //! Deserializes namespaces stored by `get_json_request_namespaces`.
+    match request_namespaces.as_ref().map(|namespaces| serde_json::from_str(namespaces)) {
+        Some(Ok(JsonValue::Object(namespaces))) => namespaces,
+        _ => JsonMap::new(),
+    }
+}
+
+fn find_undeclared_prefix(expression: &str, namespaces: &JsonMap<String, JsonValue>) -> Option<String> {
+    //! Returns first prefix used in expression, which is missing in `namespaces`.
+    //! XPath evaluation panics on such prefixes, so they must be rejected beforehand.
+    let expression = STRING_LITERAL.replace_all(expression, "''");
+    PREFIXED_NAME.captures_iter(&expression)
+        .map(|captures| captures[1].to_owned())
+        .find(|prefix| !namespaces.contains_key(prefix))
+}
+
+pub fn validate_xpath_prefixes(
+    request_xpath: &Option<String>,
+    request_namespaces: &Option<String>,
+    field_name: &str,
+    error_messages: &mut Vec<FieldError>
+) {
+    //! Checks if every prefix of XPath expressions is declared in namespaces of rule.
+    let expressions : Vec<String> = match request_xpath.as_ref().map(|request_xpath| serde_json::from_str(request_xpath)) {
+        Some(Ok(expressions)) => expressions,
+        _ => return,
+    };
+    let namespaces = parse_namespaces(request_namespaces);
+    for expression in expressions.iter() {
+        if let Some(prefix) = find_undeclared_prefix(expression, &namespaces) {
+            error_messages.push(FieldError::new(field_name, format!(
+                "{} contains XPath {} with prefix {}, which is not declared in namespaces", field_name, expression, prefix
+            )));
+        }
+    }
+}
+
+fn get_soap_action(headers: &HeaderMap) -> Option<String> {
+    //! Reads action of SOAP 1.1 request from `SOAPAction` header, or action of
+    //! SOAP 1.2 request from `action` parameter of `Content-Type`.
+    let soap_action = match headers.get("SOAPAction") {
+        Some(soap_action) => soap_action.to_str().ok()?.trim().to_owned(),
+        None => header_parameters(headers.get(header::CONTENT_TYPE)?.to_str().ok()?).into_iter()
+            .find(|(name, _)| name == "action")?.1,
+    };
+    Some(soap_action.trim_matches('"').to_owned())
+}
+
+fn xpath_matches(request_xpath: &str, request_namespaces: &Option<String>, body: &[u8]) -> bool {
+    //! Checks if every XPath expression is true for XML body. Expressions are
+    //! converted to boolean the way XPath does, so node-sets are true if not empty.
+    let expressions : Vec<String> = serde_json::from_str(request_xpath).unwrap_or_default();
+    let namespaces = parse_namespaces(request_namespaces);
+    if expressions.iter().any(|expression| find_undeclared_prefix(expression, &namespaces).is_some()) {
+        return false;
+    }
+    let package = match std::str::from_utf8(body).ok().and_then(|body| xml_parser::parse(body).ok()) {
+        Some(package) => package,
+        None => return false,
+    };
+    let document = package.as_document();
+
+    let mut context = Context::new();
+    for (prefix, uri) in namespaces.iter() {
+        context.set_namespace(prefix, uri.as_str().unwrap_or(""));
+    }
+    expressions.iter().all(|expression| match compile_xpath(expression) {
+        Ok(xpath) => xpath.evaluate(&context, document.root()).is_ok_and(|value| value.boolean()),
+        Err(_) => false,
+    })
+}
+
+pub fn xml_matches(
+    request_xpath: &Option<String>,
+    request_namespaces: &Option<String>,
+    request_soap_action: &Option<String>,
+    headers: &HeaderMap,
+    body: Option<&[u8]>
+) -> bool {
+    //! Checks if request satisfies XPath expressions and SOAP action of rule.
+    //! Rules without them match any request.
+    if let Some(request_soap_action) = request_soap_action {
+        if get_soap_action(headers).as_ref() != Some(request_soap_action) {
+            return false;
+        }
+    }
+    match (request_xpath, body) {
+        (Some(request_xpath), Some(body)) => xpath_matches(request_xpath, request_namespaces, body),
+        (Some(_), None) => false,
+        (None, _) => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::header::HeaderValue;
+
+    const ENVELOPE: &str = r#"<soap:Envelope xmlns:soap="http://schemas.xmlsoap.org/soap/envelope/" xmlns:m="urn:orders">
+        <soap:Body><m:GetOrder><m:id>42</m:id></m:GetOrder></soap:Body>
+    </soap:Envelope>"#;
+
+    fn namespaces() -> Option<String> {
+        Some(r#"{"soap": "http://schemas.xmlsoap.org/soap/envelope/", "m": "urn:orders"}"#.to_owned())
+    }
+
+    #[test]
+    fn reads_soap_1_1_action_header() {
+        let mut headers = HeaderMap::new();
+        headers.insert("SOAPAction", HeaderValue::from_static("\"urn:orders/GetOrder\""));
+
+        assert_eq!(get_soap_action(&headers).as_deref(), Some("urn:orders/GetOrder"));
+    }
+
+    #[test]
+    fn reads_soap_1_2_action_parameter() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(get_soap_action(&headers), None);
+
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/soap+xml; charset=utf-8; action=\"urn:orders/GetOrder\""));
+        assert_eq!(get_soap_action(&headers).as_deref(), Some("urn:orders/GetOrder"));
+
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/soap+xml"));
+        assert_eq!(get_soap_action(&headers), None);
+    }
+
+    #[test]
+    fn matches_when_every_expression_is_true() {
+        let body = ENVELOPE.as_bytes();
+
+        assert!(xpath_matches(r#"["//m:GetOrder", "//m:id = 42"]"#, &namespaces(), body));
+        assert!(xpath_matches("[]", &None, body));
+        assert!(!xpath_matches(r#"["//m:GetOrder", "//m:id = 43"]"#, &namespaces(), body));
+        assert!(!xpath_matches(r#"["//m:CancelOrder"]"#, &namespaces(), body));
+    }
+
+    #[test]
+    fn does_not_match_invalid_expressions_or_bodies() {
+        assert!(!xpath_matches(r#"["//m:GetOrder"]"#, &None, ENVELOPE.as_bytes()));
+        assert!(!xpath_matches(r#"["//["]"#, &namespaces(), ENVELOPE.as_bytes()));
+        assert!(!xpath_matches("[]", &None, b"<unclosed>"));
+    }
+
+    #[test]
+    fn finds_prefixes_missing_in_namespaces() {
+        let namespaces = parse_namespaces(&Some(r#"{"m": "urn:orders"}"#.to_owned()));
+
+        assert_eq!(find_undeclared_prefix("/soap:Envelope//m:id", &namespaces).as_deref(), Some("soap"));
+        assert_eq!(find_undeclared_prefix("count(//m:*) > 0 and //x:a", &namespaces).as_deref(), Some("x"));
+        assert_eq!(find_undeclared_prefix("child::m:id = 'a:b'", &namespaces), None);
+        assert_eq!(find_undeclared_prefix("//item[@id = $p:id]", &namespaces), None);
+    }
+
+    #[test]
+    fn rejects_rules_with_undeclared_prefixes() {
+        let mut error_messages = vec![];
+        let xpath = Some(r#"["//m:GetOrder", "//soap:Body"]"#.to_owned());
+
+        validate_xpath_prefixes(&xpath, &Some(r#"{"m": "urn:orders"}"#.to_owned()), "_rockery_request_xpath", &mut error_messages);
+        assert_eq!(error_messages.len(), 1);
+        assert_eq!(error_messages[0].field, "_rockery_request_xpath");
+
+        validate_xpath_prefixes(&xpath, &namespaces(), "_rockery_request_xpath", &mut error_messages);
+        assert_eq!(error_messages.len(), 1);
+    }
+
+    #[test]
+    fn requires_soap_action_and_body_when_rule_sets_them() {
+        let mut headers = HeaderMap::new();
+        headers.insert("SOAPAction", HeaderValue::from_static("urn:orders/GetOrder"));
+        let xpath = Some(r#"["//m:GetOrder"]"#.to_owned());
+
+        assert!(xml_matches(&xpath, &namespaces(), &Some("urn:orders/GetOrder".to_owned()), &headers, Some(ENVELOPE.as_bytes())));
+        assert!(!xml_matches(&None, &None, &Some("urn:orders/Cancel".to_owned()), &headers, None));
+        assert!(!xml_matches(&xpath, &namespaces(), &None, &headers, None));
+        assert!(xml_matches(&None, &None, &None, &HeaderMap::new(), None));
+    }
+}